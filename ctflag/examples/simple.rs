@@ -12,9 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ctflag::Flags;
-use std::env;
+use ctflag::{Flags, FromArg};
 
+/// Prints the flags it was given.
+#[allow(dead_code)]
 #[derive(Debug, Flags)]
 struct MyFlags {
     #[flag(desc = "Whether this is active", default = true)]
@@ -34,40 +35,20 @@ struct MyFlags {
 
     #[flag(default = "one")]
     thing: Thing,
-
-    #[flag(desc = "Prints this help message")]
-    help: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, FromArg)]
 enum Thing {
     One,
     Two,
 }
 
-impl ctflag::FromArg for Thing {
-    fn from_arg(s: &str) -> ctflag::FromArgResult<Thing> {
-        match s {
-            "one" => Ok(Thing::One),
-            "two" => Ok(Thing::Two),
-            _ => Err(ctflag::FromArgError::with_message("must be one or two")),
-        }
-    }
-}
-
 fn main() {
-    let result = MyFlags::from_args(env::args());
-    match result {
-        Ok((flags, args)) => {
+    // Handles --help, --version and errors before calling the closure.
+    ctflag::Runner::new()
+        .version(env!("CARGO_PKG_VERSION"))
+        .run(|flags: MyFlags, args| {
             println!("{:?}", flags);
             println!("{:?}", args);
-            if flags.help {
-                println!("{}", MyFlags::description());
-            }
-        }
-        Err(e) => {
-            println!("Error parsing flags: {}", e);
-            println!("{}", MyFlags::description());
-        }
-    }
+        });
 }
@@ -0,0 +1,585 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::internal::{into_string_lossy, Arg, FlagIterator, FlagStruct};
+use crate::{FlagInfo, FlagType};
+use std::env;
+use std::fmt;
+use std::io::{self, Write};
+use std::process;
+use std::str::FromStr;
+
+// Set by completion scripts when running the program to complete a value.
+const COMPLETE_ENV: &str = "CTFLAG_COMPLETE";
+
+/// A shell that completion scripts can be generated for.
+///
+/// `Shell` implements `FromStr`, so it can be used as the type of a flag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        })
+    }
+}
+
+// What to offer when completing the value of a flag.
+enum ValueHint<'a> {
+    None,
+    Files,
+    Directories,
+    Values(Vec<&'a str>),
+    // Ask the program, which calls the flag's completer.
+    Dynamic,
+}
+
+fn value_hint<'a>(flag: &FlagInfo<'a>) -> ValueHint<'a> {
+    if flag.complete.is_some() {
+        return ValueHint::Dynamic;
+    }
+    if !flag.possible_values.is_empty() {
+        return ValueHint::Values(flag.possible_values.to_vec());
+    }
+    if flag.flag_type == FlagType::Bool {
+        return ValueHint::Values(vec!["true", "false"]);
+    }
+    match flag.placeholder {
+        Some("PATH") | Some("FILE") | Some("FILENAME") => ValueHint::Files,
+        Some("DIR") | Some("DIRECTORY") => ValueHint::Directories,
+        _ => ValueHint::None,
+    }
+}
+
+/// Returns a script that completes the flags of the program `bin_name` in
+/// the given shell.
+///
+/// Flag values are completed from the flag's possible values.  Values of
+/// flags with a `PATH`, `FILE` or `FILENAME` placeholder are completed as
+/// files, and a `DIR` or `DIRECTORY` placeholder as directories.  Values of
+/// flags with a `#[flag(complete = ...)]` function are completed by running
+/// the program, see [`ctflag::handle_completion()`].
+///
+/// [`ctflag::handle_completion()`]: fn.handle_completion.html
+pub fn completion_script(
+    shell: Shell,
+    bin_name: &str,
+    flags: &[FlagInfo],
+) -> String {
+    match shell {
+        Shell::Bash => bash_script(bin_name, flags),
+        Shell::Zsh => zsh_script(bin_name, flags),
+        Shell::Fish => fish_script(bin_name, flags),
+    }
+}
+
+// Shell function names can't contain most punctuation.
+fn function_name(bin_name: &str) -> String {
+    let name = bin_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("_{}", name)
+}
+
+fn flag_names(flag: &FlagInfo) -> Vec<String> {
+    let mut names = vec![format!("--{}", flag.name)];
+    if let Some(short) = flag.short {
+        names.push(format!("-{}", short));
+    }
+    names
+}
+
+fn bash_script(bin_name: &str, flags: &[FlagInfo]) -> String {
+    let func = function_name(bin_name);
+    let mut buf = String::new();
+    buf.push_str(&format!("{}() {{\n", func));
+    buf.push_str("    local cur prev words\n");
+    buf.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    buf.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    // The words before `cur`, passed to the program to complete values.
+    buf.push_str("    words=(\"${COMP_WORDS[@]:1:COMP_CWORD-1}\")\n");
+    // Bash splits `--flag=value` into three words.
+    buf.push_str("    if [[ \"$cur\" == \"=\" ]]; then\n");
+    buf.push_str("        cur=\"\"\n");
+    buf.push_str("    elif [[ \"$prev\" == \"=\" ]]; then\n");
+    buf.push_str("        prev=\"${COMP_WORDS[COMP_CWORD-2]}\"\n");
+    buf.push_str("        words=(\"${COMP_WORDS[@]:1:COMP_CWORD-2}\")\n");
+    buf.push_str("    fi\n");
+    buf.push_str("    case \"$prev\" in\n");
+    for flag in flags.iter().filter(|f| f.takes_value()) {
+        buf.push_str(&format!("        {})\n", flag_names(flag).join("|")));
+        let reply = match value_hint(flag) {
+            ValueHint::None => String::from("()"),
+            ValueHint::Files => String::from("($(compgen -f -- \"$cur\"))"),
+            ValueHint::Directories => {
+                String::from("($(compgen -d -- \"$cur\"))")
+            }
+            ValueHint::Values(values) => {
+                format!("($(compgen -W \"{}\" -- \"$cur\"))", values.join(" "))
+            }
+            ValueHint::Dynamic => {
+                buf.push_str("            local IFS=$'\\n'\n");
+                format!(
+                    "($({}=1 \"${{COMP_WORDS[0]}}\" \"${{words[@]}}\" \"$cur\" 2>/dev/null))",
+                    COMPLETE_ENV
+                )
+            }
+        };
+        buf.push_str(&format!("            COMPREPLY={}\n", reply));
+        buf.push_str("            return 0\n");
+        buf.push_str("            ;;\n");
+    }
+    buf.push_str("    esac\n");
+    let all_names = flags
+        .iter()
+        .flat_map(flag_names)
+        .collect::<Vec<String>>()
+        .join(" ");
+    buf.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    buf.push_str(&format!(
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n",
+        all_names
+    ));
+    buf.push_str("    else\n");
+    buf.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    buf.push_str("    fi\n");
+    buf.push_str("}\n");
+    buf.push_str(&format!("complete -o filenames -F {} {}\n", func, bin_name));
+    buf
+}
+
+// Escapes text for use inside a single-quoted zsh `_arguments` spec.
+fn zsh_escape(s: &str) -> String {
+    let mut buf = String::new();
+    for c in s.chars() {
+        match c {
+            '\'' => buf.push_str("'\\''"),
+            '[' | ']' | ':' | '\\' => {
+                buf.push('\\');
+                buf.push(c);
+            }
+            _ => buf.push(c),
+        }
+    }
+    buf
+}
+
+fn zsh_script(bin_name: &str, flags: &[FlagInfo]) -> String {
+    let func = function_name(bin_name);
+    let mut buf = format!("#compdef {}\n\n{}() {{\n", bin_name, func);
+    buf.push_str("    _arguments -s \\\n");
+    for flag in flags {
+        let desc = format!("[{}]", zsh_escape(flag.description.unwrap_or("")));
+        let action = if flag.takes_value() {
+            let action = match value_hint(flag) {
+                ValueHint::None => String::from(" "),
+                ValueHint::Files => String::from("_files"),
+                ValueHint::Directories => String::from("_files -/"),
+                ValueHint::Values(values) => format!(
+                    "({})",
+                    values
+                        .iter()
+                        .map(|v| zsh_escape(v))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                ),
+                // `--flag` is passed again after the earlier words, as
+                // they end with it only when the value is a separate word.
+                ValueHint::Dynamic => format!(
+                    "{{compadd -- ${{(f)\"$({}=1 $words[1] \"${{(@)words[2,CURRENT-1]}}\" --{} \"$PREFIX\" 2>/dev/null)\"}}}}",
+                    COMPLETE_ENV, flag.name
+                ),
+            };
+            format!(
+                ":{}:{}",
+                zsh_escape(flag.placeholder.unwrap_or("VALUE")),
+                action
+            )
+        } else {
+            String::new()
+        };
+        let eq = if flag.takes_value() { "=" } else { "" };
+        match flag.short {
+            Some(short) => buf.push_str(&format!(
+                "        '(-{short} --{long})'{{-{short},--{long}{eq}}}'{desc}{action}' \\\n",
+                short = short,
+                long = flag.name,
+                eq = eq,
+                desc = desc,
+                action = action
+            )),
+            None => buf.push_str(&format!(
+                "        '--{}{}{}{}' \\\n",
+                flag.name, eq, desc, action
+            )),
+        }
+    }
+    buf.push_str("        '*:argument:_files'\n");
+    buf.push_str("}\n\n");
+    buf.push_str(&format!(
+        "if [ \"$funcstack[1]\" = \"{func}\" ]; then\n    {func} \"$@\"\nelse\n    compdef {func} {bin}\nfi\n",
+        func = func,
+        bin = bin_name
+    ));
+    buf
+}
+
+// Escapes text for use inside a single-quoted fish string.
+fn fish_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish_script(bin_name: &str, flags: &[FlagInfo]) -> String {
+    let mut buf = String::new();
+    for flag in flags {
+        let mut line = format!("complete -c {} -l {}", bin_name, flag.name);
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if flag.takes_value() {
+            line.push_str(" -r");
+            match value_hint(flag) {
+                ValueHint::None => line.push_str(" -f"),
+                ValueHint::Files => line.push_str(" -F"),
+                ValueHint::Directories => {
+                    line.push_str(" -f -a '(__fish_complete_directories)'")
+                }
+                ValueHint::Values(values) => line.push_str(&format!(
+                    " -f -a '{}'",
+                    fish_escape(&values.join(" "))
+                )),
+                ValueHint::Dynamic => {
+                    let cmd = format!(
+                        "(env {}=1 {bin} (commandline -opc)[2..-1] --{name} (commandline -ct | string replace -r -- '^--{name}=' ''))",
+                        COMPLETE_ENV,
+                        bin = bin_name,
+                        name = flag.name
+                    );
+                    line.push_str(&format!(" -f -a '{}'", fish_escape(&cmd)))
+                }
+            }
+        }
+        if let Some(desc) = flag.description {
+            line.push_str(&format!(" -d '{}'", fish_escape(desc)));
+        }
+        buf.push_str(&line);
+        buf.push('\n');
+    }
+    buf
+}
+
+/// Returns the candidates for completing the last of `words`, a partial
+/// command-line without the program name.
+///
+/// The words before the last are tokenized the same way as when parsing,
+/// to find whether the last word is the value of a flag, a flag name or a
+/// positional argument.  A flag's value is completed by its
+/// `#[flag(complete = ...)]` function if it has one, or from its possible
+/// values.  Positional arguments have no candidates.
+///
+/// ```
+/// # use ctflag::{DescribeFlags, Flags};
+/// fn complete_cluster(partial: &str) -> Vec<String> {
+///     vec![format!("{}-east", partial), format!("{}-west", partial)]
+/// }
+///
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(complete = complete_cluster)]
+///     cluster: Option<String>,
+/// }
+///
+/// # fn main() {
+/// let words = vec![String::from("--cluster"), String::from("us")];
+/// assert_eq!(
+///     ctflag::complete(&MyFlags::metadata(), &words),
+///     vec!["us-east", "us-west"]
+/// );
+/// # }
+/// ```
+pub fn complete(flags: &[FlagInfo], words: &[String]) -> Vec<String> {
+    let (partial, prior) = match words.split_last() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+
+    // The flag waiting for its value, as in `--key value`.
+    let mut pending = None;
+    for arg in FlagIterator::from_args(prior.iter().cloned()) {
+        pending = match arg {
+            Arg::Flag(FlagStruct { key, val: None }) => {
+                find_flag(flags, &key).filter(|flag| flag.takes_value())
+            }
+            _ => None,
+        };
+    }
+    if let Some(flag) = pending {
+        return complete_value(flag, partial);
+    }
+
+    if !partial.starts_with('-') {
+        return Vec::new();
+    }
+    match partial.find('=') {
+        Some(idx) => {
+            let key = &partial[..idx];
+            match find_flag(flags, key) {
+                Some(flag) => complete_value(flag, &partial[idx + 1..])
+                    .into_iter()
+                    .map(|value| format!("{}={}", key, value))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+        None => flags
+            .iter()
+            .flat_map(flag_names)
+            .filter(|name| name.starts_with(partial.as_str()))
+            .collect(),
+    }
+}
+
+// Returns the flag named `key`, as `--name` or `-s`.
+pub(crate) fn find_flag<'a, 'b>(
+    flags: &'b [FlagInfo<'a>],
+    key: &str,
+) -> Option<&'b FlagInfo<'a>> {
+    flags
+        .iter()
+        .find(|flag| flag_names(flag).iter().any(|name| name == key))
+}
+
+fn complete_value(flag: &FlagInfo, partial: &str) -> Vec<String> {
+    if let Some(complete) = flag.complete {
+        return complete(partial);
+    }
+    match value_hint(flag) {
+        ValueHint::Values(values) => values
+            .into_iter()
+            .filter(|value| value.starts_with(partial))
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Prints the candidates for completing a partial command-line and exits,
+/// if the program was run by a completion script.  Otherwise, does nothing.
+///
+/// Scripts generated by [`ctflag::completion_script()`] run the program to
+/// complete the values of flags with a `#[flag(complete = ...)]` function.
+/// The program is run with the `CTFLAG_COMPLETE` environment variable set,
+/// and the words of the partial command-line as its arguments.  The
+/// candidates found by [`ctflag::complete()`] are printed one per line.
+///
+/// This should be called before parsing the command-line, so that the
+/// program does nothing else when completing.
+///
+/// [`ctflag::completion_script()`]: fn.completion_script.html
+/// [`ctflag::complete()`]: fn.complete.html
+pub fn handle_completion(flags: &[FlagInfo]) {
+    if env::var_os(COMPLETE_ENV).is_none() {
+        return;
+    }
+    let words = env::args_os()
+        .skip(1)
+        .map(into_string_lossy)
+        .collect::<Vec<String>>();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for candidate in complete(flags, &words) {
+        let _ = writeln!(out, "{}", candidate);
+    }
+    let _ = out.flush();
+    process::exit(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::string_flag;
+
+    fn flag(name: &'static str) -> FlagInfo<'static> {
+        string_flag(name).description("It's [a] flag")
+    }
+
+    fn flags() -> Vec<FlagInfo<'static>> {
+        vec![
+            FlagInfo::new("verbose", "String", FlagType::Bool)
+                .short('v')
+                .description("It's [a] flag"),
+            flag("output").short('o').placeholder("PATH"),
+            flag("mode")
+                .placeholder("MODE")
+                .possible_values(&["fast", "slow"]),
+            flag("name").placeholder("NAME"),
+        ]
+    }
+
+    fn complete_cluster(partial: &str) -> Vec<String> {
+        vec![format!("{}-east", partial), format!("{}-west", partial)]
+    }
+
+    fn dynamic_flags() -> Vec<FlagInfo<'static>> {
+        let mut flags = flags();
+        flags.push(
+            flag("cluster")
+                .short('c')
+                .placeholder("CLUSTER")
+                .complete(complete_cluster),
+        );
+        flags
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| String::from(*w)).collect()
+    }
+
+    #[test]
+    fn test_shell_from_str() {
+        assert_eq!("zsh".parse::<Shell>(), Ok(Shell::Zsh));
+        assert!("csh".parse::<Shell>().is_err());
+    }
+
+    #[test]
+    fn test_bash() {
+        let script = completion_script(Shell::Bash, "my-prog", &flags());
+        assert!(script.starts_with("_my_prog() {\n"));
+        assert!(script.contains(
+            "        --output|-o)\n            \
+             COMPREPLY=($(compgen -f -- \"$cur\"))\n"
+        ));
+        assert!(script.contains(
+            "        --mode)\n            \
+             COMPREPLY=($(compgen -W \"fast slow\" -- \"$cur\"))\n"
+        ));
+        assert!(script
+            .contains("compgen -W \"--verbose -v --output -o --mode --name\""));
+        assert!(!script.contains("--verbose|-v)"));
+        assert!(script.ends_with("complete -o filenames -F _my_prog my-prog\n"));
+    }
+
+    #[test]
+    fn test_zsh() {
+        let script = completion_script(Shell::Zsh, "prog", &flags());
+        assert!(script.starts_with("#compdef prog\n"));
+        assert!(script.contains(
+            "'(-v --verbose)'{-v,--verbose}'[It'\\''s \\[a\\] flag]' \\\n"
+        ));
+        assert!(script.contains(
+            "'(-o --output)'{-o,--output=}'[It'\\''s \\[a\\] flag]:PATH:_files'"
+        ));
+        assert!(script
+            .contains("'--mode=[It'\\''s \\[a\\] flag]:MODE:(fast slow)'"));
+        assert!(script.contains("'--name=[It'\\''s \\[a\\] flag]:NAME: '"));
+    }
+
+    #[test]
+    fn test_fish() {
+        assert_eq!(
+            completion_script(Shell::Fish, "prog", &flags()),
+            "complete -c prog -l verbose -s v -d 'It\\'s [a] flag'\n\
+             complete -c prog -l output -s o -r -F -d 'It\\'s [a] flag'\n\
+             complete -c prog -l mode -r -f -a 'fast slow' -d 'It\\'s [a] flag'\n\
+             complete -c prog -l name -r -f -d 'It\\'s [a] flag'\n"
+        );
+    }
+
+    #[test]
+    fn test_complete_flag_names() {
+        let flags = dynamic_flags();
+        assert_eq!(complete(&flags, &words(&["--"])).len(), 5);
+        assert_eq!(complete(&flags, &words(&["--m"])), vec!["--mode"]);
+        assert_eq!(complete(&flags, &words(&["-c"])), vec!["-c"]);
+    }
+
+    #[test]
+    fn test_complete_values() {
+        let flags = dynamic_flags();
+        assert_eq!(
+            complete(&flags, &words(&["--cluster", "us"])),
+            vec!["us-east", "us-west"]
+        );
+        assert_eq!(
+            complete(&flags, &words(&["-v", "-c", "eu"])),
+            vec!["eu-east", "eu-west"]
+        );
+        // As passed by the zsh and fish scripts.
+        assert_eq!(
+            complete(&flags, &words(&["-c", "--cluster", "eu"])),
+            vec!["eu-east", "eu-west"]
+        );
+        assert_eq!(
+            complete(&flags, &words(&["--mode=fast", "--cluster", "eu"])),
+            vec!["eu-east", "eu-west"]
+        );
+        assert_eq!(complete(&flags, &words(&["--mode", "f"])), vec!["fast"]);
+        assert_eq!(
+            complete(&flags, &words(&["--cluster=us"])),
+            vec!["--cluster=us-east", "--cluster=us-west"]
+        );
+        assert_eq!(
+            complete(&flags, &words(&["--verbose=t"])),
+            vec!["--verbose=true"]
+        );
+    }
+
+    #[test]
+    fn test_complete_positionals() {
+        let flags = dynamic_flags();
+        assert!(complete(&flags, &words(&[])).is_empty());
+        assert!(complete(&flags, &words(&["us"])).is_empty());
+        assert!(complete(&flags, &words(&["--cluster=us", "us"])).is_empty());
+        assert!(complete(&flags, &words(&["--cluster", "us", "us"])).is_empty());
+        assert!(complete(&flags, &words(&["--verbose", "us"])).is_empty());
+    }
+
+    #[test]
+    fn test_dynamic_scripts() {
+        let flags = dynamic_flags();
+        assert!(completion_script(Shell::Bash, "prog", &flags).contains(
+            "        --cluster|-c)\n            \
+             local IFS=$'\\n'\n            \
+             COMPREPLY=($(CTFLAG_COMPLETE=1 \"${COMP_WORDS[0]}\" \"${words[@]}\" \"$cur\" 2>/dev/null))\n"
+        ));
+        assert!(completion_script(Shell::Zsh, "prog", &flags).contains(
+            ":CLUSTER:{compadd -- ${(f)\"$(CTFLAG_COMPLETE=1 $words[1] \"${(@)words[2,CURRENT-1]}\" --cluster \"$PREFIX\" 2>/dev/null)\"}}'"
+        ));
+        assert!(completion_script(Shell::Fish, "prog", &flags).contains(
+            "-f -a '(env CTFLAG_COMPLETE=1 prog (commandline -opc)[2..-1] --cluster (commandline -ct | string replace -r -- \\'^--cluster=\\' \\'\\'))'"
+        ));
+    }
+}
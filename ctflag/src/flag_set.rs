@@ -0,0 +1,494 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::internal::{
+    at_index, bool_from_arg, unrecognized_arg, Arg, FlagIterator, FlagStruct,
+};
+use crate::{
+    Completer, FlagError, FlagInfo, FlagType, FromArg, FromArgResult,
+    GroupInfo, HelpRenderer, MissingValueStruct, ParseErrorStruct, Result,
+};
+use std::any::{self, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+type Parser = Box<dyn Fn(&str) -> FromArgResult<Box<dyn Any>>>;
+
+/// A flag registered at runtime with a [`ctflag::FlagSet`].
+///
+/// [`ctflag::FlagSet`]: struct.FlagSet.html
+pub struct Flag {
+    name: String,
+    short: Option<char>,
+    type_name: &'static str,
+    flag_type: FlagType,
+    placeholder: Option<String>,
+    description: Option<String>,
+    default: Option<String>,
+    group: Option<String>,
+    possible_values: &'static [&'static str],
+    complete: Option<Completer>,
+    parser: Parser,
+}
+
+impl Flag {
+    /// Creates a flag named `name` whose value is parsed as type `T`.
+    ///
+    /// Unless a default is set with [`ctflag::Flag::default()`], a flag that
+    /// is not on the command-line has no value.  `bool` flags default to
+    /// `false`.
+    ///
+    /// [`ctflag::Flag::default()`]: struct.Flag.html#method.default
+    pub fn new<T>(name: &str) -> Self
+    where
+        T: FromArg + 'static,
+    {
+        let flag_type = flag_type_of::<T>();
+        Flag {
+            name: String::from(name),
+            short: None,
+            type_name: any::type_name::<T>(),
+            flag_type,
+            placeholder: None,
+            description: None,
+            default: None,
+            group: None,
+            possible_values: <T as FromArg>::possible_values(),
+            complete: None,
+            parser: Box::new(|s| {
+                <T as FromArg>::from_arg(s).map(|v| Box::new(v) as Box<dyn Any>)
+            }),
+        }
+    }
+
+    /// A short, single character alias for the flag name.
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    /// The text that appears in place of the flag's value in the help text.
+    /// Defaults to "VALUE".
+    pub fn placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = Some(String::from(placeholder));
+        self
+    }
+
+    /// The description of the flag, displayed in the help text.
+    pub fn desc(mut self, desc: &str) -> Self {
+        self.description = Some(String::from(desc));
+        self
+    }
+
+    /// The value of the flag if it is not set on the command-line.  The
+    /// value is parsed the same way as values given on the command-line, and
+    /// [`ctflag::FlagSet::parse()`] returns a [`ctflag::FlagError::ParseError`]
+    /// if it is invalid and the flag isn't set.
+    ///
+    /// [`ctflag::FlagSet::parse()`]: struct.FlagSet.html#method.parse
+    /// [`ctflag::FlagError::ParseError`]: enum.FlagError.html#variant.ParseError
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = Some(String::from(default));
+        self
+    }
+
+    /// Lists the flag in a separate section of the help text, under the
+    /// given heading.
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = Some(String::from(group));
+        self
+    }
+
+    /// Completes the flag's value at runtime with `complete`.  See
+    /// [`ctflag::handle_completion()`].
+    ///
+    /// [`ctflag::handle_completion()`]: fn.handle_completion.html
+    pub fn complete(mut self, complete: Completer) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
+    fn info(&self) -> FlagInfo<'_> {
+        let is_bool = self.flag_type == FlagType::Bool;
+        FlagInfo {
+            name: &self.name,
+            short: self.short,
+            type_name: self.type_name,
+            flag_type: self.flag_type,
+            placeholder: if is_bool {
+                None
+            } else {
+                Some(self.placeholder.as_deref().unwrap_or("VALUE"))
+            },
+            description: self.description.as_deref(),
+            default: self.default.as_deref(),
+            possible_values: self.possible_values,
+            optional: !is_bool && self.default.is_none(),
+            group: self.group.as_deref(),
+            complete: self.complete,
+            min: None,
+            max: None,
+            conflicts_with: &[],
+            requires: &[],
+            required_unless: &[],
+            required_if: &[],
+        }
+    }
+
+    fn parse(&self, input: String) -> Result<Box<dyn Any>> {
+        (self.parser)(&input).map_err(|err| {
+            FlagError::ParseError(ParseErrorStruct {
+                flag: self.name.clone(),
+                type_str: self.type_name,
+                input,
+                src: err,
+                index: None,
+            })
+        })
+    }
+}
+
+impl fmt::Debug for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Flag")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+/// A set of flags defined at runtime, for when the flags aren't known
+/// when the program is compiled.
+///
+/// Flags are parsed with the same rules as types deriving
+/// [`ctflag::Flags`], and the values are looked up by name and type.
+///
+/// ```
+/// # use ctflag::{Flag, FlagSet};
+/// # fn main() -> ctflag::Result<()> {
+/// let mut flags = FlagSet::new();
+/// flags.add(Flag::new::<i64>("threads").short('t').default("4"));
+/// flags.add(Flag::new::<String>("output").placeholder("PATH"));
+/// flags.add(Flag::new::<bool>("verbose").desc("Prints more output"));
+///
+/// let args = vec!["prog", "-t", "8", "--verbose"];
+/// let (values, rest) =
+///     flags.parse(args.into_iter().map(String::from))?;
+/// assert_eq!(values.get::<i64>("threads"), Some(&8));
+/// assert_eq!(values.get::<String>("output"), None);
+/// assert_eq!(values.get::<bool>("verbose"), Some(&true));
+/// assert_eq!(rest, vec!["prog"]);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ctflag::Flags`]: trait.Flags.html
+#[derive(Debug, Default)]
+pub struct FlagSet {
+    flags: Vec<Flag>,
+    groups: Vec<(String, String)>,
+}
+
+impl FlagSet {
+    pub fn new() -> Self {
+        FlagSet::default()
+    }
+
+    /// Adds a flag to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a flag with the same name or short name was already added.
+    pub fn add(&mut self, flag: Flag) -> &mut Self {
+        if let Some(other) = self.flags.iter().find(|other| {
+            other.name == flag.name
+                || (flag.short.is_some() && other.short == flag.short)
+        }) {
+            panic!("flag \"{}\" conflicts with \"{}\"", flag.name, other.name);
+        }
+        self.flags.push(flag);
+        self
+    }
+
+    /// Gives the help section named `name` a description.
+    pub fn add_group(&mut self, name: &str, desc: &str) -> &mut Self {
+        self.groups.push((String::from(name), String::from(desc)));
+        self
+    }
+
+    /// Consumes the command-line arguments and returns the values of the
+    /// flags and the arguments that were not consumed, the same way as
+    /// [`ctflag::Flags::from_args()`].
+    ///
+    /// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+    pub fn parse<T>(&self, args: T) -> Result<(FlagValues, Vec<String>)>
+    where
+        T: IntoIterator<Item = String>,
+    {
+        let mut values = HashMap::new();
+        let mut rest_args = Vec::<String>::new();
+        // Skip the first arg (program name) and pass it through.
+        let mut args = args.into_iter();
+        if let Some(arg) = args.next() {
+            rest_args.push(arg);
+        }
+        let mut iter = FlagIterator::from_args(args);
+        while let Some(arg) = iter.next() {
+            match arg {
+                Arg::Arg(arg) => rest_args.push(arg),
+                Arg::Flag(flag_arg) => {
+                    let index = iter.index();
+                    let (flag, value) = self
+                        .parse_flag(flag_arg, &mut iter)
+                        .map_err(|err| at_index(err, index))?;
+                    values.insert(flag.name.clone(), value);
+                }
+            }
+        }
+
+        for flag in &self.flags {
+            if values.contains_key(&flag.name) {
+                continue;
+            }
+            if let Some(default) = &flag.default {
+                // The default comes from runtime data, so a bad one is
+                // reported like a bad value rather than a panic.
+                let value = flag.parse(default.clone())?;
+                values.insert(flag.name.clone(), value);
+            } else if flag.flag_type == FlagType::Bool {
+                values.insert(flag.name.clone(), Box::new(false));
+            }
+        }
+        Ok((FlagValues { values }, rest_args))
+    }
+
+    // Parses the value of the flag `flag_arg`, taking it from the next
+    // argument if it isn't given with `=`.
+    fn parse_flag<T>(
+        &self,
+        flag_arg: FlagStruct,
+        iter: &mut FlagIterator<T>,
+    ) -> Result<(&Flag, Box<dyn Any>)>
+    where
+        T: Iterator<Item = String>,
+    {
+        let flag = match self.find(&flag_arg.key) {
+            Some(flag) => flag,
+            None => {
+                return Err(unrecognized_arg(
+                    flag_arg.key,
+                    self.flags.iter().map(|flag| flag.name.as_str()),
+                ))
+            }
+        };
+        let value: Box<dyn Any> = if flag.flag_type == FlagType::Bool {
+            Box::new(bool_from_arg(flag_arg.val.as_deref()).map_err(|err| {
+                FlagError::ParseError(ParseErrorStruct {
+                    flag: flag.name.clone(),
+                    type_str: "bool",
+                    input: flag_arg.val.unwrap(),
+                    src: err,
+                    index: None,
+                })
+            })?)
+        } else {
+            let input =
+                flag_arg.val.or_else(|| iter.next_arg()).ok_or_else(|| {
+                    FlagError::MissingValue(MissingValueStruct {
+                        flag: flag.name.clone(),
+                        index: None,
+                    })
+                })?;
+            flag.parse(input)?
+        };
+        Ok((flag, value))
+    }
+
+    /// Returns a description of each flag in the set, in the order they
+    /// were added.
+    pub fn metadata(&self) -> Vec<FlagInfo<'_>> {
+        self.flags.iter().map(Flag::info).collect()
+    }
+
+    /// Returns the descriptions given to help sections.
+    pub fn groups(&self) -> Vec<GroupInfo<'_>> {
+        self.groups
+            .iter()
+            .map(|(name, desc)| GroupInfo {
+                name,
+                description: Some(desc),
+            })
+            .collect()
+    }
+
+    /// Returns a String that describes the flags in the set, in the same
+    /// format as [`ctflag::DescribeFlags::description()`].
+    ///
+    /// [`ctflag::DescribeFlags::description()`]: trait.DescribeFlags.html#method.description
+    pub fn description(&self) -> String {
+        HelpRenderer::new().render_flags(&self.metadata(), &self.groups())
+    }
+
+    fn find(&self, key: &str) -> Option<&Flag> {
+        if let Some(long) = key.strip_prefix("--") {
+            self.flags.iter().find(|flag| flag.name == long)
+        } else {
+            let mut chars = key.strip_prefix('-')?.chars();
+            let short = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            self.flags.iter().find(|flag| flag.short == Some(short))
+        }
+    }
+}
+
+/// The values of flags parsed by a [`ctflag::FlagSet`].
+///
+/// [`ctflag::FlagSet`]: struct.FlagSet.html
+#[derive(Debug)]
+pub struct FlagValues {
+    values: HashMap<String, Box<dyn Any>>,
+}
+
+impl FlagValues {
+    /// Returns the value of the flag named `name`, or `None` if the flag has
+    /// no value or its value is not of type `T`.
+    pub fn get<T: 'static>(&self, name: &str) -> Option<&T> {
+        self.values.get(name)?.downcast_ref::<T>()
+    }
+
+    /// Returns true if the flag named `name` has a value, either from the
+    /// command-line or from its default.
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+}
+
+fn flag_type_of<T: 'static>() -> FlagType {
+    let id = TypeId::of::<T>();
+    let is_any = |ids: &[TypeId]| ids.contains(&id);
+    if id == TypeId::of::<bool>() {
+        FlagType::Bool
+    } else if is_any(&[
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<isize>(),
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+    ]) {
+        FlagType::Int
+    } else if is_any(&[TypeId::of::<f32>(), TypeId::of::<f64>()]) {
+        FlagType::Float
+    } else if id == TypeId::of::<String>() {
+        FlagType::String
+    } else {
+        FlagType::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag_set() -> FlagSet {
+        let mut flags = FlagSet::new();
+        flags
+            .add(Flag::new::<i32>("count").default("3").desc("How many"))
+            .add(Flag::new::<String>("name").short('n').placeholder("NAME"))
+            .add(Flag::new::<bool>("verbose"));
+        flags
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().cloned().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let (values, rest) = flag_set().parse(args(&["prog"])).unwrap();
+        assert_eq!(values.get::<i32>("count"), Some(&3));
+        assert_eq!(values.get::<String>("name"), None);
+        assert_eq!(values.get::<bool>("verbose"), Some(&false));
+        assert_eq!(rest, vec!["prog"]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let (values, rest) = flag_set()
+            .parse(args(&["prog", "--count=7", "-n", "bob", "--verbose", "x"]))
+            .unwrap();
+        assert_eq!(values.get::<i32>("count"), Some(&7));
+        assert_eq!(values.get::<String>("name").unwrap(), "bob");
+        assert_eq!(values.get::<bool>("verbose"), Some(&true));
+        assert_eq!(values.get::<i64>("count"), None);
+        assert_eq!(rest, vec!["prog", "x"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_matches!(
+            flag_set().parse(args(&["prog", "--count=many"])),
+            Err(FlagError::ParseError(_))
+        );
+        assert_matches!(
+            flag_set().parse(args(&["prog", "--name"])),
+            Err(FlagError::MissingValue(_))
+        );
+        assert_matches!(
+            flag_set().parse(args(&["prog", "--nope"])),
+            Err(FlagError::UnrecognizedArg(_))
+        );
+        assert_matches!(
+            flag_set().parse(args(&["prog", "--cuont=1"])),
+            Err(FlagError::UnrecognizedArg(err)),
+            err.arg == "--cuont"
+                && err.suggestion.as_deref() == Some("--count")
+        );
+    }
+
+    #[test]
+    fn test_bad_default() {
+        let mut flags = FlagSet::new();
+        flags.add(Flag::new::<i32>("count").default("many"));
+        assert_matches!(
+            flags.parse(args(&["prog"])),
+            Err(FlagError::ParseError(err)),
+            err.flag == "count" && err.input == "many" && err.index.is_none()
+        );
+        assert!(flags.parse(args(&["prog", "--count=2"])).is_ok());
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            flag_set().description(),
+            "OPTIONS:\n\
+             \x20     --count VALUE    How many (defaults to 3)\n\
+             \x20 -n, --name [NAME]\n\
+             \x20     --verbose\n"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicate_flag() {
+        flag_set().add(Flag::new::<u8>("count"));
+    }
+}
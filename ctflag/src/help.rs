@@ -0,0 +1,459 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::style::{ColorChoice, Stream, Style};
+use crate::{DescribeFlags, FlagInfo, GroupInfo};
+use std::env;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Used when the width of the terminal can't be determined.
+const DEFAULT_WIDTH: usize = 80;
+
+// Descriptions are never wrapped narrower than this, even when long flag
+// names leave less room on the line.
+const MIN_DESC_WIDTH: usize = 20;
+
+/// Renders the help text of a type implementing [`ctflag::DescribeFlags`] at
+/// runtime.
+///
+/// By default the output is identical to
+/// [`ctflag::DescribeFlags::description()`].  When a width is set,
+/// descriptions that don't fit on the line are wrapped with a hanging indent
+/// aligned to the description column.  When color is enabled, headers, flag
+/// names and placeholders are highlighted.
+///
+/// ```
+/// # use ctflag::{Flags, HelpRenderer};
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(desc = "The floopy floops the whoop")]
+///     enable_floopy: bool,
+/// }
+/// # fn main() {
+/// let help = HelpRenderer::new()
+///     .width(40)
+///     .color(ctflag::ColorChoice::Auto)
+///     .render::<MyFlags>();
+/// # }
+/// ```
+///
+/// [`ctflag::DescribeFlags`]: trait.DescribeFlags.html
+/// [`ctflag::DescribeFlags::description()`]: trait.DescribeFlags.html#method.description
+#[derive(Clone, Debug, Default)]
+pub struct HelpRenderer {
+    width: Option<usize>,
+    color: ColorChoice,
+}
+
+impl HelpRenderer {
+    /// Creates a renderer that doesn't wrap descriptions or use color.
+    pub fn new() -> Self {
+        HelpRenderer {
+            width: None,
+            color: ColorChoice::Never,
+        }
+    }
+
+    /// Creates a renderer that wraps descriptions to the width returned by
+    /// [`ctflag::terminal_width()`].
+    ///
+    /// [`ctflag::terminal_width()`]: fn.terminal_width.html
+    pub fn for_terminal() -> Self {
+        HelpRenderer::new().width(terminal_width())
+    }
+
+    /// Wraps descriptions so that lines don't exceed `width` columns.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Sets whether the help text is styled.  [`ctflag::ColorChoice::Auto`]
+    /// styles the help text if stdout is a terminal.
+    ///
+    /// [`ctflag::ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Renders the help text for the flags defined by `F`.
+    pub fn render<F: DescribeFlags>(&self) -> String {
+        self.render_flags(&F::metadata(), &F::groups())
+    }
+
+    /// Renders the help text for a list of flags and the sections they are
+    /// grouped into.  Flags without a group are listed under `OPTIONS:`,
+    /// which is left out when every flag has a group.
+    pub fn render_flags(
+        &self,
+        flags: &[FlagInfo],
+        groups: &[GroupInfo],
+    ) -> String {
+        let style = Style::new(self.color.should_color(Stream::Stdout));
+        let mut sections = sections(flags);
+        if sections.is_empty() {
+            sections.push((None, Vec::new()));
+        }
+
+        let mut buf = String::new();
+        for (i, (group, section)) in sections.iter().enumerate() {
+            if i > 0 {
+                buf.push('\n');
+            }
+            match group {
+                Some(name) => {
+                    buf.push_str(&style.header(&format!("{}:", name)));
+                    buf.push('\n');
+                    if let Some(desc) = group_description(groups, name) {
+                        buf.push_str("  ");
+                        self.push_wrapped(&mut buf, desc, 2);
+                        buf.push('\n');
+                    }
+                }
+                None => {
+                    buf.push_str(&style.header("OPTIONS:"));
+                    buf.push('\n');
+                }
+            }
+            self.render_section(&mut buf, section, style);
+        }
+        buf
+    }
+
+    // Flags are aligned within each section.
+    fn render_section(
+        &self,
+        buf: &mut String,
+        flags: &[&FlagInfo],
+        style: Style,
+    ) {
+        let widths = flags
+            .iter()
+            .map(|flag| text_width(&flag_name(flag, Style::plain())))
+            .collect::<Vec<usize>>();
+        let col_width = widths.iter().cloned().max().unwrap_or(0);
+        let desc_col = 2 + col_width + 4;
+
+        for (flag, name_width) in flags.iter().zip(widths) {
+            buf.push_str("  ");
+            buf.push_str(&flag_name(flag, style));
+            let text = description_text(flag);
+            if !text.is_empty() {
+                buf.push_str("    ");
+                push_spaces(buf, col_width - name_width);
+                self.push_wrapped(buf, &text, desc_col);
+            }
+            buf.push('\n');
+        }
+    }
+
+    // Appends `text`, which starts at column `indent`, wrapping it with a
+    // hanging indent if it doesn't fit within the width.
+    fn push_wrapped(&self, buf: &mut String, text: &str, indent: usize) {
+        match self.width {
+            Some(width) if indent + text_width(text) > width => {
+                let text_cols =
+                    width.saturating_sub(indent).max(MIN_DESC_WIDTH);
+                for (i, line) in wrap(text, text_cols).iter().enumerate() {
+                    if i > 0 {
+                        buf.push('\n');
+                        push_spaces(buf, indent);
+                    }
+                    buf.push_str(line);
+                }
+            }
+            _ => buf.push_str(text),
+        }
+    }
+}
+
+// Splits flags into the sections of the help text.  Ungrouped flags come
+// first, followed by each group in the order its first flag was declared.
+pub(crate) fn sections<'a, 'b>(
+    flags: &'b [FlagInfo<'a>],
+) -> Vec<(Option<&'a str>, Vec<&'b FlagInfo<'a>>)> {
+    let mut sections: Vec<(Option<&str>, Vec<&FlagInfo>)> = Vec::new();
+    for flag in flags {
+        match sections.iter_mut().find(|(group, _)| *group == flag.group) {
+            Some((_, section)) => section.push(flag),
+            None => sections.push((flag.group, vec![flag])),
+        }
+    }
+    sections.sort_by_key(|(group, _)| group.is_some());
+    sections
+}
+
+// Returns the description of the group named `name`, if it has one.
+pub(crate) fn group_description<'a>(
+    groups: &[GroupInfo<'a>],
+    name: &str,
+) -> Option<&'a str> {
+    groups
+        .iter()
+        .find(|g| g.name == name)
+        .and_then(|g| g.description)
+}
+
+// The description of a flag followed by its default, possible values, bounds
+// and the flags it conflicts with, requires or is required by.
+pub(crate) fn description_text(flag: &FlagInfo) -> String {
+    let mut text = String::from(flag.description.unwrap_or(""));
+    if let Some(def) = flag.default_text() {
+        text.push_str(&format!(" (defaults to {})", def));
+    }
+    if !flag.possible_values.is_empty() {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&format!(
+            "[possible values: {}]",
+            flag.possible_values.join(", ")
+        ));
+    }
+    let bounds = match (flag.min, flag.max) {
+        (Some(min), Some(max)) => format!("min: {}, max: {}", min, max),
+        (Some(min), None) => format!("min: {}", min),
+        (None, Some(max)) => format!("max: {}", max),
+        (None, None) => String::new(),
+    };
+    if !bounds.is_empty() {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&format!("[{}]", bounds));
+    }
+    let relations = [
+        ("conflicts with", flag.conflicts_with),
+        ("requires", flag.requires),
+        ("required unless", flag.required_unless),
+    ];
+    for (relation, names) in relations.iter() {
+        if names.is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let names = names
+            .iter()
+            .map(|name| format!("--{}", name))
+            .collect::<Vec<String>>();
+        text.push_str(&format!("[{}: {}]", relation, names.join(", ")));
+    }
+    if !flag.required_if.is_empty() {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let conditions = flag
+            .required_if
+            .iter()
+            .map(|(name, value)| format!("--{}={}", name, value))
+            .collect::<Vec<String>>();
+        text.push_str(&format!("[required if: {}]", conditions.join(", ")));
+    }
+    text
+}
+
+/// Returns the width of the terminal in columns.
+///
+/// The `COLUMNS` environment variable takes precedence. Otherwise the size of
+/// the terminal attached to stdout is used, falling back to 80 columns if
+/// stdout is not a terminal.
+pub fn terminal_width() -> usize {
+    let columns = env::var("COLUMNS")
+        .ok()
+        .and_then(|val| val.trim().parse::<usize>().ok())
+        .filter(|&cols| cols > 0);
+    if let Some(cols) = columns {
+        return cols;
+    }
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(cols), _)) if cols > 0 => cols as usize,
+        _ => DEFAULT_WIDTH,
+    }
+}
+
+fn flag_name(flag: &FlagInfo, style: Style) -> String {
+    let mut buf = String::new();
+    match flag.short {
+        Some(short) => {
+            buf.push_str(&style.literal(&format!("-{}", short)));
+            buf.push_str(", ");
+        }
+        None => buf.push_str("    "),
+    }
+    buf.push_str(&style.literal(&format!("--{}", flag.name)));
+    match flag.placeholder {
+        Some(placeholder) if flag.optional => {
+            buf.push_str(&format!(" [{}]", style.placeholder(placeholder)))
+        }
+        Some(placeholder) => {
+            buf.push_str(&format!(" {}", style.placeholder(placeholder)))
+        }
+        None => {}
+    }
+    buf
+}
+
+fn text_width(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+fn push_spaces(buf: &mut String, count: usize) {
+    for _ in 0..count {
+        buf.push(' ');
+    }
+}
+
+// Splits `text` into lines of at most `width` graphemes, breaking at
+// whitespace.  Words longer than `width` are kept intact on their own line.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = text_width(word);
+        if line_width > 0 && line_width + 1 + word_width > width {
+            lines.push(line);
+            line = String::new();
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    if line_width > 0 {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::string_flag;
+    use crate::FlagType;
+
+    fn flags() -> Vec<FlagInfo<'static>> {
+        vec![
+            FlagInfo::new("verbose", "bool", FlagType::Bool)
+                .short('v')
+                .description(
+                    "Prints lots and lots of extra output while running",
+                ),
+            string_flag("output")
+                .placeholder("PATH")
+                .description("Output file")
+                .optional(true),
+            FlagInfo::new("count", "i64", FlagType::Int)
+                .placeholder("INTEGER")
+                .default("34"),
+        ]
+    }
+
+    #[test]
+    fn test_render_unwrapped() {
+        assert_eq!(
+            HelpRenderer::new().render_flags(&flags(), &[]),
+            "OPTIONS:\n\
+             \x20 -v, --verbose          \
+             Prints lots and lots of extra output while running\n\
+             \x20     --output [PATH]    Output file\n\
+             \x20     --count INTEGER     (defaults to 34)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_wrapped() {
+        assert_eq!(
+            HelpRenderer::new().width(50).render_flags(&flags(), &[]),
+            "OPTIONS:\n\
+             \x20 -v, --verbose          Prints lots and lots of\n\
+             \x20                        extra output while\n\
+             \x20                        running\n\
+             \x20     --output [PATH]    Output file\n\
+             \x20     --count INTEGER     (defaults to 34)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        assert_eq!(
+            HelpRenderer::new()
+                .color(ColorChoice::Always)
+                .render_flags(&flags()[1..], &[]),
+            "\x1b[1;4mOPTIONS:\x1b[0m\n\
+             \x20     \x1b[1m--output\x1b[0m [\x1b[36mPATH\x1b[0m]    \
+             Output file\n\
+             \x20     \x1b[1m--count\x1b[0m \x1b[36mINTEGER\x1b[0m     \
+             (defaults to 34)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_groups() {
+        let flag = |name| {
+            FlagInfo::new(name, "bool", FlagType::Bool)
+                .description("Does things")
+        };
+        let flags = [
+            flag("net_timeout").group("Networking"),
+            flag("a"),
+            flag("port").group("Networking"),
+            flag("debug").group("Debugging"),
+        ];
+        let groups = [GroupInfo {
+            name: "Networking",
+            description: Some("Controls the connection"),
+        }];
+        assert_eq!(
+            HelpRenderer::new().render_flags(&flags, &groups),
+            "OPTIONS:\n\
+             \x20     --a    Does things\n\
+             \n\
+             Networking:\n\
+             \x20 Controls the connection\n\
+             \x20     --net_timeout    Does things\n\
+             \x20     --port           Does things\n\
+             \n\
+             Debugging:\n\
+             \x20     --debug    Does things\n"
+        );
+    }
+
+    #[test]
+    fn test_render_all_grouped() {
+        let flags = [flags()[1].clone().group("Networking")];
+        assert_eq!(
+            HelpRenderer::new().render_flags(&flags, &[]),
+            "Networking:\n\
+             \x20     --output [PATH]    Output file\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_counts_graphemes() {
+        assert_eq!(wrap("héllo wörld", 11), vec!["héllo wörld"]);
+        assert_eq!(wrap("héllo wörld", 10), vec!["héllo", "wörld"]);
+    }
+
+    #[test]
+    fn test_wrap_long_word() {
+        assert_eq!(wrap("a verylongword b", 5), vec!["a", "verylongword", "b"]);
+    }
+}
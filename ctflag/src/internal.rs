@@ -12,34 +12,211 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{FromArg, FromArgError, FromArgResult};
+use crate::{
+    ConflictStruct, FlagError, FlagErrors, FromArg, FromArgError, FromArgOs,
+    FromArgResult, InvalidChoiceStruct, InvalidUtf8Struct,
+    MissingRequiredStruct, OutOfRangeStruct, ParseErrorStruct,
+    RequiredCondition, RequiresStruct, Result, UnrecognizedArgStruct,
+    ValidationStruct,
+};
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 
-pub enum Arg {
-    Arg(String),
-    Flag(FlagStruct),
+pub enum Arg<S: RawArg = String> {
+    Arg(S),
+    Flag(FlagStruct<S>),
 }
 
-pub struct FlagStruct {
-    pub key: String,
-    pub val: Option<String>,
+pub struct FlagStruct<S: RawArg = String> {
+    pub key: S::Key,
+    pub val: Option<S>,
+}
+
+// An argument as passed to the program, either a `String`, an `OsString` or
+// a `&str` borrowed from the caller.
+pub trait RawArg: Sized {
+    // The name of a flag, which borrows from the argument if it can.
+    type Key: AsRef<str> + Into<String>;
+
+    // Splits the argument into the name and value of a flag, or returns it
+    // back if it isn't a flag.
+    fn into_flag(self) -> std::result::Result<FlagStruct<Self>, Self>;
+}
+
+impl RawArg for String {
+    type Key = String;
+
+    fn into_flag(self) -> std::result::Result<FlagStruct<Self>, Self> {
+        if !self.starts_with("-") {
+            return Err(self);
+        }
+        Ok(match self.find("=") {
+            Some(idx) => FlagStruct {
+                key: String::from(&self[0..idx]),
+                val: Some(String::from(&self[idx + 1..self.len()])),
+            },
+            None => FlagStruct {
+                key: self,
+                val: None,
+            },
+        })
+    }
+}
+
+impl<'a> RawArg for &'a str {
+    type Key = &'a str;
+
+    fn into_flag(self) -> std::result::Result<FlagStruct<Self>, Self> {
+        if !self.starts_with("-") {
+            return Err(self);
+        }
+        Ok(match self.find("=") {
+            Some(idx) => FlagStruct {
+                key: &self[0..idx],
+                val: Some(&self[idx + 1..]),
+            },
+            None => FlagStruct {
+                key: self,
+                val: None,
+            },
+        })
+    }
+}
+
+impl RawArg for OsString {
+    type Key = String;
+
+    fn into_flag(self) -> std::result::Result<FlagStruct<Self>, Self> {
+        match self.into_string() {
+            Ok(arg) => arg
+                .into_flag()
+                .map(|flag| FlagStruct {
+                    key: flag.key,
+                    val: flag.val.map(OsString::from),
+                })
+                .map_err(OsString::from),
+            Err(arg) => split_os_flag(arg),
+        }
+    }
+}
+
+// The value of a flag as parsed by the derive, which must be valid UTF-8
+// unless its field takes an `OsStr`.
+pub trait ArgValue: RawArg + AsRef<OsStr> {
+    // The value once checked to be valid UTF-8.
+    type Str: AsRef<str> + Into<String>;
+
+    fn into_str(self, flag: &str) -> Result<Self::Str>;
+}
+
+impl ArgValue for OsString {
+    type Str = String;
+
+    fn into_str(self, flag: &str) -> Result<String> {
+        self.into_string().map_err(|input| {
+            FlagError::InvalidUtf8(InvalidUtf8Struct {
+                flag: String::from(flag),
+                input,
+                index: None,
+            })
+        })
+    }
+}
+
+impl<'a> ArgValue for &'a str {
+    type Str = &'a str;
+
+    fn into_str(self, _flag: &str) -> Result<&'a str> {
+        Ok(self)
+    }
+}
+
+// Splits an argument that isn't valid UTF-8, keeping the value as is.  The
+// name of the flag is converted lossily, since it can't match any flag.
+#[cfg(unix)]
+fn split_os_flag(
+    arg: OsString,
+) -> std::result::Result<FlagStruct<OsString>, OsString> {
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    let bytes = arg.as_bytes();
+    if !bytes.starts_with(b"-") {
+        return Err(arg);
+    }
+    Ok(match bytes.iter().position(|&b| b == b'=') {
+        Some(idx) => FlagStruct {
+            key: String::from_utf8_lossy(&bytes[..idx]).into_owned(),
+            val: Some(OsString::from_vec(bytes[idx + 1..].to_vec())),
+        },
+        None => FlagStruct {
+            key: String::from_utf8_lossy(bytes).into_owned(),
+            val: None,
+        },
+    })
+}
+
+#[cfg(windows)]
+fn split_os_flag(
+    arg: OsString,
+) -> std::result::Result<FlagStruct<OsString>, OsString> {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    let wide = arg.encode_wide().collect::<Vec<u16>>();
+    if wide.first() != Some(&u16::from(b'-')) {
+        return Err(arg);
+    }
+    Ok(match wide.iter().position(|&c| c == u16::from(b'=')) {
+        Some(idx) => FlagStruct {
+            key: String::from_utf16_lossy(&wide[..idx]),
+            val: Some(OsString::from_wide(&wide[idx + 1..])),
+        },
+        None => FlagStruct {
+            key: String::from_utf16_lossy(&wide),
+            val: None,
+        },
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn split_os_flag(
+    arg: OsString,
+) -> std::result::Result<FlagStruct<OsString>, OsString> {
+    match arg.to_string_lossy().into_owned().into_flag() {
+        Ok(flag) => Ok(FlagStruct {
+            key: flag.key,
+            val: flag.val.map(OsString::from),
+        }),
+        Err(_) => Err(arg),
+    }
 }
 
 pub struct FlagIterator<T>
 where
-    T: Iterator<Item = String>,
+    T: Iterator,
+    T::Item: RawArg,
 {
     inner: T,
+    // The number of arguments returned so far, which is the index in argv
+    // of the last one since argv[0] is skipped before the iterator is made.
+    index: usize,
 }
 
 impl<T> FlagIterator<T>
 where
-    T: Iterator<Item = String>,
+    T: Iterator,
+    T::Item: RawArg,
 {
     pub fn from_args(args: T) -> Self {
-        FlagIterator { inner: args }
+        FlagIterator {
+            inner: args,
+            index: 0,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
     }
 
-    pub fn next_arg(&mut self) -> Option<String> {
+    pub fn next_arg(&mut self) -> Option<T::Item> {
         match self.next() {
             Some(Arg::Arg(val)) => Some(val),
             _ => None,
@@ -49,45 +226,423 @@ where
 
 impl<T> Iterator for FlagIterator<T>
 where
-    T: Iterator<Item = String>,
+    T: Iterator,
+    T::Item: RawArg,
 {
-    type Item = Arg;
+    type Item = Arg<T::Item>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let arg = self.inner.next()?;
-        if !arg.starts_with("-") {
-            Some(Arg::Arg(arg))
-        } else {
-            Some(Arg::Flag(match arg.find("=") {
-                Some(idx) => FlagStruct {
-                    key: String::from(&arg[0..idx]),
-                    val: Some(String::from(&arg[idx + 1..arg.len()])),
-                },
-                None => FlagStruct {
-                    key: arg,
-                    val: None,
-                },
-            }))
-        }
+        self.index += 1;
+        Some(match arg.into_flag() {
+            Ok(flag) => Arg::Flag(flag),
+            Err(arg) => Arg::Arg(arg),
+        })
     }
 }
 
 pub fn bool_from_arg(s: Option<&str>) -> FromArgResult<bool> {
     match s {
-        Some(s) => s.parse::<bool>().map_err(|_| FromArgError::new()),
+        Some(s) => s.parse::<bool>().map_err(FromArgError::with_source),
         None => Ok(true),
     }
 }
 
+// Parses the value of a bool flag, which is true if no value was given.
+pub fn parse_bool<S: ArgValue>(flag: &str, value: Option<S>) -> Result<bool> {
+    let value = value.map(|value| value.into_str(flag)).transpose()?;
+    let input = value.as_ref().map(AsRef::as_ref);
+    bool_from_arg(input)
+        .map_err(|err| parse_error(flag, "bool", value.unwrap(), err))
+}
+
+// Parses the value of a flag with `parse`, which is `FromArg::from_arg` or
+// `option_from_arg`.
+pub fn parse_value<S, T>(
+    flag: &str,
+    type_str: &'static str,
+    input: S,
+    parse: fn(&str) -> FromArgResult<T>,
+) -> Result<T>
+where
+    S: AsRef<str> + Into<String>,
+{
+    parse(input.as_ref()).map_err(|err| parse_error(flag, type_str, input, err))
+}
+
+pub fn parse_error<S: Into<String>>(
+    flag: &str,
+    type_str: &'static str,
+    input: S,
+    src: FromArgError,
+) -> FlagError {
+    FlagError::ParseError(ParseErrorStruct {
+        flag: String::from(flag),
+        type_str,
+        input: input.into(),
+        src,
+        index: None,
+    })
+}
+
 pub fn option_from_arg<T: FromArg>(s: &str) -> FromArgResult<Option<T>> {
     <T as FromArg>::from_arg(s).map(Some)
 }
 
+pub fn option_from_arg_os<T: FromArgOs>(s: &OsStr) -> FromArgResult<Option<T>> {
+    <T as FromArgOs>::from_arg_os(s).map(Some)
+}
+
+// Converts the arguments left after parsing back into strings, which they
+// were before being passed to `Flags::parse_args_os`.
+pub fn string_args<F>((flags, rest): (F, Vec<OsString>)) -> (F, Vec<String>) {
+    (flags, rest.into_iter().map(into_string_lossy).collect())
+}
+
+// Converts `arg` into a string, copying it only if it isn't valid UTF-8.
+pub fn into_string_lossy(arg: OsString) -> String {
+    arg.into_string()
+        .unwrap_or_else(|arg| arg.to_string_lossy().into_owned())
+}
+
+// Passes `input` through if it is one of `choices`.
+pub fn check_choice<S>(
+    flag: &str,
+    input: S,
+    choices: &'static [&'static str],
+) -> Result<S>
+where
+    S: AsRef<str> + Into<String>,
+{
+    if choices.contains(&input.as_ref()) {
+        return Ok(input);
+    }
+    Err(FlagError::InvalidChoice(InvalidChoiceStruct {
+        flag: String::from(flag),
+        suggestion: suggest(input.as_ref(), choices.iter().cloned()),
+        input: input.into(),
+        choices,
+        index: None,
+    }))
+}
+
+// Passes `value` through if it is within the bounds.
+pub fn check_range<T>(
+    flag: &str,
+    value: T,
+    min: Option<T>,
+    max: Option<T>,
+) -> Result<T>
+where
+    T: PartialOrd + fmt::Display,
+{
+    // A value that can't be compared to a bound, like NaN, is out of range.
+    let outside = |bound: &Option<T>, side| {
+        bound
+            .as_ref()
+            .is_some_and(|bound| match value.partial_cmp(bound) {
+                Some(ord) => ord == side,
+                None => true,
+            })
+    };
+    let too_small = outside(&min, Ordering::Less);
+    let too_large = outside(&max, Ordering::Greater);
+    if !too_small && !too_large {
+        return Ok(value);
+    }
+    Err(FlagError::OutOfRange(OutOfRangeStruct {
+        flag: String::from(flag),
+        value: value.to_string(),
+        min: min.map(|min| min.to_string()),
+        max: max.map(|max| max.to_string()),
+        index: None,
+    }))
+}
+
+// The flags set on the command-line, in order, with their index in argv.
+pub type Seen<'a> = [(&'a str, usize)];
+
+// Returns the index in argv of the first time `flag` was set.
+fn index_of(seen: &Seen, flag: &str) -> Option<usize> {
+    seen.iter()
+        .find(|(name, _)| *name == flag)
+        .map(|&(_, index)| index)
+}
+
+// Fails if both `a` and `b` were set.  The flag set last gets blamed.
+pub fn check_conflict(seen: &Seen, a: &str, b: &str) -> Result<()> {
+    match (index_of(seen, a), index_of(seen, b)) {
+        (Some(index_a), Some(index_b)) if index_a > index_b => {
+            Err(conflict(a, b, index_a))
+        }
+        (Some(_), Some(index_b)) => Err(conflict(b, a, index_b)),
+        _ => Ok(()),
+    }
+}
+
+// Fails if `flag` was set without `required`.
+pub fn check_requires(seen: &Seen, flag: &str, required: &str) -> Result<()> {
+    match index_of(seen, flag) {
+        Some(index) if index_of(seen, required).is_none() => {
+            Err(FlagError::Requires(RequiresStruct {
+                flag: String::from(flag),
+                required: String::from(required),
+                index: Some(index),
+            }))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Fails if more than one of `flags` was set or, when `required`, none were.
+pub fn check_one_of(seen: &Seen, flags: &[&str], required: bool) -> Result<()> {
+    let mut set = seen.iter().filter(|(flag, _)| flags.contains(flag));
+    match set.next() {
+        Some((first, _)) => match set.find(|(flag, _)| flag != first) {
+            Some((second, index)) => Err(conflict(second, first, *index)),
+            None => Ok(()),
+        },
+        None if required => Err(FlagError::MissingOneOf(
+            flags.iter().map(|flag| String::from(*flag)).collect(),
+        )),
+        None => Ok(()),
+    }
+}
+
+// Fails if `flag` was not set and neither was any of `unless`.
+pub fn check_required_unless(
+    seen: &Seen,
+    flag: &str,
+    unless: &[&str],
+) -> Result<()> {
+    let is_set = |flag: &str| index_of(seen, flag).is_some();
+    if is_set(flag) || unless.iter().any(|other| is_set(other)) {
+        return Ok(());
+    }
+    Err(FlagError::MissingRequired(MissingRequiredStruct {
+        flag: String::from(flag),
+        condition: RequiredCondition::Unless(
+            unless.iter().map(|other| String::from(*other)).collect(),
+        ),
+    }))
+}
+
+// Fails if `flag` was not set although `other` has the value `value`, which
+// the generated code has already compared.
+pub fn check_required_if(
+    seen: &Seen,
+    flag: &str,
+    other: &str,
+    value: &str,
+    has_value: bool,
+) -> Result<()> {
+    if !has_value || index_of(seen, flag).is_some() {
+        return Ok(());
+    }
+    Err(FlagError::MissingRequired(MissingRequiredStruct {
+        flag: String::from(flag),
+        condition: RequiredCondition::If {
+            flag: String::from(other),
+            value: String::from(value),
+        },
+    }))
+}
+
+fn conflict(flag: &str, other: &str, index: usize) -> FlagError {
+    FlagError::Conflict(ConflictStruct {
+        flag: String::from(flag),
+        other: String::from(other),
+        index: Some(index),
+    })
+}
+
+// Collects the errors found by the generated `from_args`.  When failing fast,
+// the first error stops parsing.
+pub struct Errors {
+    errors: Vec<FlagError>,
+    fail_fast: bool,
+}
+
+impl Errors {
+    pub fn new(fail_fast: bool) -> Self {
+        Errors {
+            errors: Vec::new(),
+            fail_fast,
+        }
+    }
+
+    // Records the error of `result`, if any.  Returns the errors so far if
+    // parsing should stop.
+    pub fn check(
+        &mut self,
+        result: Result<()>,
+    ) -> std::result::Result<(), FlagErrors> {
+        if let Err(err) = result {
+            self.errors.push(err);
+            if self.fail_fast {
+                return self.finish();
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the errors found, if there are any.
+    pub fn finish(&mut self) -> std::result::Result<(), FlagErrors> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(FlagErrors {
+            errors: std::mem::take(&mut self.errors),
+        })
+    }
+}
+
+// Returns the first of the errors returned when failing fast.
+pub fn first_error(errors: FlagErrors) -> FlagError {
+    errors.into_iter().next().unwrap()
+}
+
+// Records the index in argv of the flag that caused `err`, unless the error
+// already has one.
+pub fn at_index(mut err: FlagError, index: usize) -> FlagError {
+    let err_index = match &mut err {
+        FlagError::ParseError(err) => &mut err.index,
+        FlagError::InvalidUtf8(err) => &mut err.index,
+        FlagError::MissingValue(err) => &mut err.index,
+        FlagError::UnrecognizedArg(err) => &mut err.index,
+        FlagError::InvalidChoice(err) => &mut err.index,
+        FlagError::OutOfRange(err) => &mut err.index,
+        FlagError::Validation(err) => &mut err.index,
+        FlagError::Conflict(err) => &mut err.index,
+        FlagError::Requires(err) => &mut err.index,
+        FlagError::MissingOneOf(_) | FlagError::MissingRequired(_) => {
+            return err
+        }
+    };
+    if err_index.is_none() {
+        *err_index = Some(index);
+    }
+    err
+}
+
+// Runs the validator of a single flag.
+pub fn validate<T>(
+    flag: &str,
+    value: &T,
+    validator: fn(&T) -> std::result::Result<(), String>,
+) -> Result<()> {
+    validator(value).map_err(|msg| {
+        FlagError::Validation(ValidationStruct {
+            flag: Some(String::from(flag)),
+            msg,
+            index: None,
+        })
+    })
+}
+
+// Runs the validator of a flag that wasn't set, whose default value wasn't
+// checked while parsing.
+pub fn validate_default<T>(
+    seen: &Seen,
+    flag: &str,
+    value: &T,
+    validator: fn(&T) -> std::result::Result<(), String>,
+) -> Result<()> {
+    match index_of(seen, flag) {
+        Some(_) => Ok(()),
+        None => validate(flag, value, validator),
+    }
+}
+
+// Runs the struct-level validator, once every flag is parsed.
+pub fn validate_flags<F>(
+    flags: &F,
+    validator: fn(&F) -> std::result::Result<(), String>,
+) -> Result<()> {
+    validator(flags).map_err(|msg| {
+        FlagError::Validation(ValidationStruct {
+            flag: None,
+            msg,
+            index: None,
+        })
+    })
+}
+
+// The message of the error returned when a value isn't one of `choices`.
+pub fn expected_one_of(input: &str, choices: &[&str]) -> String {
+    let mut msg = format!("expected one of: {}", choices.join(", "));
+    if let Some(suggestion) = suggest(input, choices.iter().cloned()) {
+        msg.push_str(&format!("; did you mean \"{}\"?", suggestion));
+    }
+    msg
+}
+
+// The error returned for the flag `arg`, which isn't one of `names`.  Long
+// flags that look like a typo of one of the names suggest it.
+pub fn unrecognized_arg<'a, I>(arg: String, names: I) -> FlagError
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let suggestion = arg
+        .strip_prefix("--")
+        .and_then(|long| suggest(long, names))
+        .map(|name| format!("--{}", name));
+    FlagError::UnrecognizedArg(UnrecognizedArgStruct {
+        arg,
+        suggestion,
+        index: None,
+    })
+}
+
+// Returns the candidate closest to `input`, if it is close enough that
+// `input` is likely a typo of it.
+pub fn suggest<'a, I>(input: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// The number of insertions, deletions, substitutions and transpositions of
+// adjacent characters needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    // dist[i][j] is the distance between a[..i] and b[..j].
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut d = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(dist[i - 2][j - 2] + 1);
+            }
+            dist[i][j] = d;
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn test_flag_iterator() {
         let mut iter = FlagIterator::from_args(
             vec![
@@ -113,4 +668,78 @@ mod tests {
         assert_matches!(iter.next(), Some(Arg::Arg(arg)), arg == "true");
         assert_matches!(iter.next(), None);
     }
+
+    #[test]
+    fn test_into_string_lossy() {
+        let arg = String::from("--flag=value");
+        let ptr = arg.as_ptr();
+        let arg = into_string_lossy(OsString::from(arg));
+        assert_eq!(arg.as_ptr(), ptr);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let arg = OsString::from_vec(vec![b'a', 0xff]);
+            assert_eq!(into_string_lossy(arg), "a\u{fffd}");
+        }
+    }
+
+    #[test]
+    fn test_flag_iterator_borrows() {
+        let args = ["--flag=value", "--two"];
+        let mut iter = FlagIterator::from_args(args.iter().cloned());
+        assert_matches!(
+            iter.next(),
+            Some(Arg::Flag(f)),
+            f.key.as_ptr() == args[0].as_ptr()
+                && matches!(
+                    f.val,
+                    Some(val),
+                    val.as_ptr() == args[0][7..].as_ptr()
+                )
+        );
+        assert_matches!(
+            iter.next(),
+            Some(Arg::Flag(f)),
+            f.key.as_ptr() == args[1].as_ptr() && f.val.is_none()
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("fast", "fast"), 0);
+        assert_eq!(edit_distance("fsat", "fast"), 1);
+        assert_eq!(edit_distance("fas", "fast"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let choices = ["fast", "safe", "debug"];
+        assert_eq!(suggest("fsat", choices.iter().cloned()), Some("fast"));
+        assert_eq!(suggest("debgu", choices.iter().cloned()), Some("debug"));
+        assert_eq!(suggest("slow", choices.iter().cloned()), None);
+    }
+
+    #[test]
+    fn test_check_one_of() {
+        let flags = ["json", "yaml"];
+        assert!(check_one_of(&[("json", 1), ("json", 2)], &flags, true).is_ok());
+        assert!(check_one_of(&[("other", 1)], &flags, false).is_ok());
+        assert_matches!(
+            check_one_of(&[("other", 1)], &flags, true),
+            Err(FlagError::MissingOneOf(flags)),
+            flags == ["json", "yaml"]
+        );
+        assert_matches!(
+            check_one_of(
+                &[("yaml", 1), ("other", 2), ("json", 4)],
+                &flags,
+                false
+            ),
+            Err(FlagError::Conflict(err)),
+            err.flag == "json" && err.other == "yaml" && err.index == Some(4)
+        );
+    }
 }
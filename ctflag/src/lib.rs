@@ -53,19 +53,74 @@
 //! # }
 //! ```
 //!
-//! A description of the flags, suitable for use in a help message, can be obtained
-//! by calling the [`ctflag::Flags::description()`] method.
+//! A description of the flags, suitable for use in a help message, comes from
+//! the [`ctflag::DescribeFlags`] trait, which the derive implements as well.
+//! The [`ctflag::DescribeFlags::description()`] method returns it, and the
+//! [`ctflag::DescribeFlags::wrapped_description()`] method wraps long
+//! descriptions to the width of the terminal.  The
+//! [`ctflag::DescribeFlags::metadata()`] method describes each flag in a
+//! structured form, for building other kinds of documentation.  A man page is
+//! returned by the [`ctflag::DescribeFlags::man_page()`] method, and a
+//! Markdown or HTML reference page by the
+//! [`ctflag::DescribeFlags::markdown_reference()`] and
+//! [`ctflag::DescribeFlags::html_reference()`] methods, using the doc comment
+//! of the struct to describe the program.  The
+//! [`ctflag::DescribeFlags::json_schema()`] method describes the flags as a
+//! JSON Schema, for tools that launch the program.
 //!
 //! The behaviour of each flag can be changed using the `#[flag(...)]` attribute.
 //!
 //! - `desc = "..."`: Provides a description of the flag, displayed in the
-//!   help text by the [`ctflag::Flags::description()`] method.
+//!   help text by the [`ctflag::DescribeFlags::description()`] method.
 //! - `placeholder = "..."`: Provides the text that appears in place of the
 //!   flag's value in the help text. Defaults to "VALUE".
 //! - `default = ...`: For types other than `Optional`, provides a default
 //!   value if the flag is not set on the command-line. This only works with type
-//!   literals (bool, i64, str, etc.).
+//!   literals (bool, i64, str, etc.).  For other types the default is a string,
+//!   parsed with [`ctflag::FromArg`] along with the arguments.  A default that
+//!   doesn't parse is reported as a [`ctflag::FlagError::ParseError`].
 //! - `short = '...'`: A short, single character alias for the flag name.
+//! - `group = "..."`: Lists the flag in a separate section of the help text,
+//!   under the given heading. Sections appear in the order their first flag is
+//!   declared, after any flags without a group.  The `OPTIONS:` section of
+//!   flags without a group is left out if every flag has one.
+//! - `choices = ["...", ...]`: For `String` and `Option<String>` types, rejects
+//!   values other than the given ones. The choices are listed in the help text
+//!   and offered by shell completions.
+//! - `min = ...`, `max = ...`: For integer and float types, rejects values
+//!   outside of the bounds, which must be number literals.  Can also be
+//!   written as `range = 1..=256`, `range = 1..` or `range = ..=256`.
+//! - `complete = path::to_fn`: A function of type `fn(&str) -> Vec<String>`
+//!   that completes the flag's value at runtime, given the partial value.
+//!   See [`ctflag::DescribeFlags::handle_completion()`].
+//! - `validate = path::to_fn`: A function of type
+//!   `fn(&T) -> Result<(), String>` that checks the value of the flag,
+//!   including its default when the flag isn't set.  For `Option<T>` fields,
+//!   it is only called when the flag is set.  The message it returns is
+//!   reported with a [`ctflag::FlagError::Validation`].
+//! - `conflicts_with = "..."`: The name of a flag, or an array of names, that
+//!   cannot be set together with this one.
+//! - `requires = "..."`: The name of a flag, or an array of names, that must be
+//!   set whenever this one is.
+//! - `required_unless = "..."`: The name of a flag, or an array of names.  This
+//!   flag must be set unless one of them is, where a `bool` flag is only set
+//!   when it is true.
+//! - `required_if = ("...", "...")`: The name of another flag and a value.
+//!   This flag must be set when the other flag has that value, compared as
+//!   text with [`ctflag::ToArg`].  Can be repeated.
+//! - `os`: Parses the value with [`ctflag::FromArgOs`] instead of
+//!   [`ctflag::FromArg`], which is already the case for `PathBuf` and
+//!   `OsString` types.
+//!
+//! A section can be given a description with the struct-level
+//! `#[flags(group(name = "...", desc = "..."))]` attribute.  Checks that involve
+//! several flags are given with `#[flags(validate = path::to_fn)]`, a function
+//! of type `fn(&Self) -> Result<(), String>` called once every flag is parsed.
+//! `#[flags(at_most_one_of = ["...", ...])]` allows at most one of the named
+//! flags to be set, and `#[flags(one_of = [...])]` requires exactly one of them.
+//! Both attributes can be repeated, and the relationships between flags are
+//! noted in the help text.  A `bool` flag only counts as set when it is true,
+//! so `--json=false` doesn't conflict with `--yaml`.
 //!
 //! ```
 //! # use ctflag::Flags;
@@ -87,25 +142,68 @@
 //! # fn main() {}
 //! ```
 //!
+//! Flags that are only known at runtime can be defined with a
+//! [`ctflag::FlagSet`] instead of a struct.
+//!
+//! Deriving [`ctflag::ToArgs`] as well converts the flags back into
+//! command-line arguments, for logging or forwarding them to a child process.
+//!
 //! The type of each field must implement the [`ctflag::FromArg`] trait.  A blanket
-//! implementation of this trait exists for any type implementing the `FromStr` trait.
+//! implementation of this trait exists for any type implementing the `FromStr` trait
+//! with an error that implements `std::error::Error`, which is kept as the source
+//! of the [`ctflag::FlagError`].
+//!
+//! The struct can have type parameters, for flags that are reused with
+//! different types.  A field whose type uses a type parameter, like `T`,
+//! `Id<T>` or an `Option` of either, adds a [`ctflag::FromArg`] bound on that
+//! type, as well as `Default` unless the field has a default value.
+//!
+//! ```
+//! # use ctflag::Flags;
+//! ##[derive(Flags)]
+//! struct LookupFlags<Id> {
+//!     #[flag(desc = "The record to look up")]
+//!     id: Option<Id>,
+//! }
+//!
+//! # fn main() -> ctflag::Result<()> {
+//! let args = vec!["prog", "--id=42"].into_iter().map(String::from);
+//! let (flags, _) = LookupFlags::<u64>::from_args(args)?;
+//! assert_eq!(flags.id, Some(42));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ctflag::Flags::from_args_os()`] takes the arguments as `OsString`s, so
+//! that file names that aren't valid UTF-8 reach `PathBuf` and `OsString`
+//! fields unchanged through the [`ctflag::FromArgOs`] trait.
+//!
+//! The [`ctflag::BorrowedFlags`] trait parses arguments given as `&str`
+//! without copying them, and lets fields of type `&str` borrow from them, for
+//! programs that parse many command-lines.
+//!
+//! For enums without fields, the trait can be derived.  Each variant is parsed
+//! from its name in kebab-case, and the accepted values are listed in the help
+//! text.  The `#[arg(...)]` attribute changes how values are matched.
+//!
+//! - `rename = "..."`: On a variant, replaces the name it is parsed from.
+//! - `alias = "..."`: On a variant, another name it is parsed from.  Can be
+//!   repeated.
+//! - `ignore_case`: On the enum, matches names regardless of case.
+//!
+//! The derive does not implement [`ctflag::ToArg`].  Implement `Display` to
+//! write each variant as a name it is parsed from, which is needed for the
+//! enum to be used by [`ctflag::ToArgs`] or compared by `required_if`.
 //!
 //! ```
-//! # use ctflag::{Flags, FromArg, FromArgError, FromArgResult};
-//! // A custom type.
+//! # use ctflag::{Flags, FromArg};
+//! // A custom type, parsed from "apple", "orange" or "blood-orange".
+//! ##[derive(FromArg)]
 //! enum Fruit {
 //!     Apple,
+//!     #[arg(alias = "satsuma")]
 //!     Orange,
-//! }
-//!
-//! impl FromArg for Fruit {
-//!     fn from_arg(s: &str) -> FromArgResult<Self> {
-//!         match s {
-//!             "apple" => Ok(Fruit::Apple),
-//!             "orange" => Ok(Fruit::Orange),
-//!             _ => Err(FromArgError::with_message("bad fruit")),
-//!         }
-//!     }
+//!     BloodOrange,
 //! }
 //!
 //! impl Default for Fruit {
@@ -123,10 +221,31 @@
 //!
 //! [`ctflag::Flags`]: trait.Flags.html
 //! [`ctflag::FromArg`]: trait.FromArg.html
-//! [`ctflag::Flags::description()`]: trait.Flags.html#tymethod.description
+//! [`ctflag::FromArgOs`]: trait.FromArgOs.html
+//! [`ctflag::BorrowedFlags`]: trait.BorrowedFlags.html
+//! [`ctflag::Flags::from_args_os()`]: trait.Flags.html#method.from_args_os
+//! [`ctflag::FlagError`]: enum.FlagError.html
+//! [`ctflag::FlagError::Validation`]: enum.FlagError.html#variant.Validation
+//! [`ctflag::FlagError::ParseError`]: enum.FlagError.html#variant.ParseError
+//! [`ctflag::FlagSet`]: struct.FlagSet.html
+//! [`ctflag::ToArgs`]: trait.ToArgs.html
+//! [`ctflag::ToArg`]: trait.ToArg.html
+//! [`ctflag::DescribeFlags`]: trait.DescribeFlags.html
+//! [`ctflag::DescribeFlags::description()`]: trait.DescribeFlags.html#method.description
+//! [`ctflag::DescribeFlags::wrapped_description()`]: trait.DescribeFlags.html#method.wrapped_description
+//! [`ctflag::DescribeFlags::handle_completion()`]: trait.DescribeFlags.html#method.handle_completion
+//! [`ctflag::DescribeFlags::man_page()`]: trait.DescribeFlags.html#method.man_page
+//! [`ctflag::DescribeFlags::markdown_reference()`]: trait.DescribeFlags.html#method.markdown_reference
+//! [`ctflag::DescribeFlags::html_reference()`]: trait.DescribeFlags.html#method.html_reference
+//! [`ctflag::DescribeFlags::json_schema()`]: trait.DescribeFlags.html#method.json_schema
+//! [`ctflag::DescribeFlags::metadata()`]: trait.DescribeFlags.html#tymethod.metadata
 
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 
 // Define the required shared macros first. Definition order is
 // important for macros.
@@ -142,18 +261,197 @@ pub use ctflag_derive::*;
 #[doc(hidden)]
 pub mod internal;
 
+mod completion;
+mod flag_set;
+mod help;
+mod man;
+mod metadata;
+mod reference;
+mod report;
+mod runner;
+mod schema;
+mod style;
+
+pub use completion::{complete, completion_script, handle_completion, Shell};
+pub use flag_set::{Flag, FlagSet, FlagValues};
+pub use help::{terminal_width, HelpRenderer};
+pub use man::man_page;
+pub use metadata::{Completer, FlagInfo, FlagType, GroupInfo};
+pub use reference::{html_reference, markdown_reference};
+pub use report::ErrorRenderer;
+pub use runner::{run, Runner};
+pub use schema::json_schema;
+pub use style::{ColorChoice, Stream};
+
+use style::Style;
+
+/// The error returned when the command-line arguments can't be parsed.
+///
+/// Besides the message given by its `Display` implementation, the error can
+/// be inspected with [`ctflag::FlagError::kind()`],
+/// [`ctflag::FlagError::flag()`] and [`ctflag::FlagError::index()`].
+///
+/// [`ctflag::FlagError::kind()`]: enum.FlagError.html#method.kind
+/// [`ctflag::FlagError::flag()`]: enum.FlagError.html#method.flag
+/// [`ctflag::FlagError::index()`]: enum.FlagError.html#method.index
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum FlagError {
     ParseError(ParseErrorStruct),
-    MissingValue(String),
-    UnrecognizedArg(String),
+    InvalidUtf8(InvalidUtf8Struct),
+    MissingValue(MissingValueStruct),
+    UnrecognizedArg(UnrecognizedArgStruct),
+    InvalidChoice(InvalidChoiceStruct),
+    OutOfRange(OutOfRangeStruct),
+    Validation(ValidationStruct),
+    Conflict(ConflictStruct),
+    Requires(RequiresStruct),
+    MissingOneOf(Vec<String>),
+    MissingRequired(MissingRequiredStruct),
+}
+
+/// The errors returned by [`ctflag::Flags::from_args_all()`], in the order
+/// they were found.  There is always at least one.
+///
+/// [`ctflag::Flags::from_args_all()`]: trait.Flags.html#method.from_args_all
+#[derive(Clone, Debug)]
+pub struct FlagErrors {
+    errors: Vec<FlagError>,
+}
+
+/// The kind of a [`ctflag::FlagError`], which stays the same when its message
+/// changes.  New kinds may be added, so matches need a wildcard arm.
+///
+/// [`ctflag::FlagError`]: enum.FlagError.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Parse,
+    InvalidUtf8,
+    MissingValue,
+    UnrecognizedArg,
+    InvalidChoice,
+    OutOfRange,
+    Validation,
+    Conflict,
+    Requires,
+    MissingOneOf,
+    MissingRequired,
 }
 
+/// The value of a flag could not be parsed into the type of its field.
 #[derive(Clone, Debug)]
 pub struct ParseErrorStruct {
+    pub flag: String,
     pub type_str: &'static str,
     pub input: String,
     pub src: FromArgError,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// The value of a flag was not valid UTF-8, and the type of its field
+/// doesn't implement [`ctflag::FromArgOs`].
+///
+/// [`ctflag::FromArgOs`]: trait.FromArgOs.html
+#[derive(Clone, Debug)]
+pub struct InvalidUtf8Struct {
+    pub flag: String,
+    pub input: OsString,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// A flag that takes a value was the last argument.
+#[derive(Clone, Debug)]
+pub struct MissingValueStruct {
+    pub flag: String,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// A flag that isn't defined was passed on the command-line.
+#[derive(Clone, Debug)]
+pub struct UnrecognizedArgStruct {
+    pub arg: String,
+    /// The defined flag closest to the argument, if it looks like a typo.
+    pub suggestion: Option<String>,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// The value of a flag declared with `#[flag(choices = [...])]` was not one
+/// of the choices.
+#[derive(Clone, Debug)]
+pub struct InvalidChoiceStruct {
+    pub flag: String,
+    pub input: String,
+    pub choices: &'static [&'static str],
+    /// The choice closest to the input, if it looks like a typo.
+    pub suggestion: Option<&'static str>,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// The value of a flag declared with `#[flag(min = ..., max = ...)]` was
+/// outside of its bounds.
+#[derive(Clone, Debug)]
+pub struct OutOfRangeStruct {
+    pub flag: String,
+    pub value: String,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// A function given with `#[flag(validate = ...)]` or
+/// `#[flags(validate = ...)]` rejected the flags.
+#[derive(Clone, Debug)]
+pub struct ValidationStruct {
+    /// The flag that was rejected, or `None` if the struct-level validator
+    /// rejected the flags.
+    pub flag: Option<String>,
+    pub msg: String,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// Two flags that cannot be used together were both set.
+#[derive(Clone, Debug)]
+pub struct ConflictStruct {
+    /// The flag that was set last.
+    pub flag: String,
+    pub other: String,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// A flag declared with `#[flag(requires = ...)]` was set without the flag
+/// it requires.
+#[derive(Clone, Debug)]
+pub struct RequiresStruct {
+    pub flag: String,
+    pub required: String,
+    /// The index in argv of the flag, if known.
+    pub index: Option<usize>,
+}
+
+/// A flag declared with `#[flag(required_unless = ...)]` or
+/// `#[flag(required_if = ...)]` was not set although its condition held.
+#[derive(Clone, Debug)]
+pub struct MissingRequiredStruct {
+    pub flag: String,
+    pub condition: RequiredCondition,
+}
+
+/// The reason a flag was required.
+#[derive(Clone, Debug)]
+pub enum RequiredCondition {
+    /// None of these flags was set.
+    Unless(Vec<String>),
+    /// The flag `flag` was set to `value`.
+    If { flag: String, value: String },
 }
 
 pub type Result<T> = std::result::Result<T, FlagError>;
@@ -184,7 +482,7 @@ pub type Result<T> = std::result::Result<T, FlagError>;
 /// the purpose of this crate.
 ///
 /// [`ctflag::FromArgs`]: trait.FromArgs.html
-pub trait Flags: Sized {
+pub trait Flags: DescribeFlags {
     /// Consumes the command-line arguments and returns a tuple containing
     /// the value of this type, and a list of command-line
     /// arguments that were not consumed.  These arguments typically include
@@ -213,6 +511,119 @@ pub trait Flags: Sized {
     where
         T: IntoIterator<Item = String>;
 
+    /// Like [`ctflag::Flags::from_args()`], but keeps parsing after an error
+    /// and returns every error found, so they can be reported together.
+    ///
+    /// Only `#[derive(Flags)]` keeps parsing after an error.  A `Flags`
+    /// implemented by hand parses with `from_args()`, so this returns just
+    /// the first error it finds.
+    ///
+    /// ```
+    /// # use ctflag::Flags;
+    /// ##[derive(Flags)]
+    /// struct MyFlags {
+    ///     count: i32,
+    ///     ratio: f64,
+    /// }
+    ///
+    /// # fn main() {
+    /// let args = vec!["prog", "--count=x", "--ratio=y"];
+    /// let args = args.into_iter().map(String::from);
+    /// let errors = MyFlags::from_args_all(args).err().unwrap();
+    /// assert_eq!(errors.len(), 2);
+    /// eprintln!("{}", errors);
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+    fn from_args_all<T>(
+        args: T,
+    ) -> std::result::Result<(Self, Vec<String>), FlagErrors>
+    where
+        T: IntoIterator<Item = String>,
+    {
+        Self::parse_args_os(args.into_iter().map(OsString::from), false)
+            .map(internal::string_args)
+    }
+
+    /// Like [`ctflag::Flags::from_args()`], but takes the arguments as
+    /// `OsString`s, as returned by `std::env::args_os()`, so that arguments
+    /// that aren't valid UTF-8 don't cause a panic.
+    ///
+    /// Fields whose type implements [`ctflag::FromArgOs`], like `PathBuf`
+    /// and `OsString`, receive the value of their flag as is.  The value of
+    /// any other flag must be valid UTF-8, or
+    /// [`ctflag::FlagError::InvalidUtf8`] is returned.  The arguments that
+    /// were not consumed are returned as is.
+    ///
+    /// ```
+    /// # use ctflag::Flags;
+    /// # use std::path::PathBuf;
+    /// ##[derive(Flags)]
+    /// struct MyFlags {
+    ///     output: Option<PathBuf>,
+    /// }
+    ///
+    /// # fn main() -> ctflag::Result<()> {
+    /// let (flags, args) = MyFlags::from_args_os(std::env::args_os())?;
+    /// if let Some(output) = flags.output {
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+    /// [`ctflag::FromArgOs`]: trait.FromArgOs.html
+    /// [`ctflag::FlagError::InvalidUtf8`]: enum.FlagError.html#variant.InvalidUtf8
+    fn from_args_os<T>(args: T) -> Result<(Self, Vec<OsString>)>
+    where
+        T: IntoIterator<Item = OsString>,
+    {
+        Self::parse_args_os(args, true).map_err(internal::first_error)
+    }
+
+    // Parses the arguments, stopping at the first error if `fail_fast` is
+    // set.  Implemented by the derive so that the parsing code is shared by
+    // `from_args`, `from_args_all` and `from_args_os`.  The default calls
+    // `from_args`, which always stops at the first error, so hand-written
+    // impls report one error even when `fail_fast` is unset.
+    #[doc(hidden)]
+    fn parse_args_os<T>(
+        args: T,
+        fail_fast: bool,
+    ) -> std::result::Result<(Self, Vec<OsString>), FlagErrors>
+    where
+        T: IntoIterator<Item = OsString>,
+    {
+        let _ = fail_fast;
+        let args = args.into_iter().map(internal::into_string_lossy);
+        let (flags, rest) = Self::from_args(args)?;
+        Ok((flags, rest.into_iter().map(OsString::from).collect()))
+    }
+}
+
+/// Describes the flags of a struct for help text and generated documentation.
+/// Implemented by `#[derive(Flags)]` for every struct, including those with a
+/// lifetime, which implement [`ctflag::BorrowedFlags`] instead of
+/// [`ctflag::Flags`].
+///
+/// ```
+/// # use ctflag::{DescribeFlags, Flags};
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(desc = "The floopy floops the whoop")]
+///     enable_floopy: bool,
+/// }
+///
+/// # fn main() {
+/// assert_eq!(MyFlags::metadata()[0].name, "enable_floopy");
+/// # }
+/// ```
+///
+/// [`ctflag::BorrowedFlags`]: trait.BorrowedFlags.html
+/// [`ctflag::Flags`]: trait.Flags.html
+pub trait DescribeFlags: Sized {
     /// Returns a String that describes the flags defined in the struct
     /// implementing this trait.
     ///
@@ -246,21 +657,320 @@ pub trait Flags: Sized {
     ///   -o, --output [PATH]    Output file
     ///   --slomps INTEGER       How many slomps to include (defaults to 34)
     /// ```
-    fn description() -> String;
+    fn description() -> String {
+        HelpRenderer::new().render::<Self>()
+    }
+
+    /// Returns the same String as
+    /// [`ctflag::DescribeFlags::description()`], with descriptions wrapped to
+    /// fit the width of the terminal.
+    ///
+    /// See [`ctflag::terminal_width()`] for how the width is determined, and
+    /// [`ctflag::HelpRenderer`] for wrapping to a specific width.
+    ///
+    /// [`ctflag::DescribeFlags::description()`]: trait.DescribeFlags.html#method.description
+    /// [`ctflag::terminal_width()`]: fn.terminal_width.html
+    /// [`ctflag::HelpRenderer`]: struct.HelpRenderer.html
+    fn wrapped_description() -> String {
+        HelpRenderer::for_terminal().render::<Self>()
+    }
+
+    /// Returns a description of each flag defined in the struct
+    /// implementing this trait, in declaration order.
+    ///
+    /// This is the same information used to produce
+    /// [`ctflag::DescribeFlags::description()`], and can be used to generate
+    /// other kinds of documentation.
+    ///
+    /// [`ctflag::DescribeFlags::description()`]: trait.DescribeFlags.html#method.description
+    fn metadata() -> Vec<FlagInfo<'static>>;
+
+    /// Returns the help sections declared with `#[flags(group(...))]`.
+    fn groups() -> Vec<GroupInfo<'static>> {
+        Vec::new()
+    }
+
+    /// Returns the doc comment of the struct implementing this trait, which
+    /// describes the program in generated documentation like man pages.
+    fn about() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns a script for `shell` that completes the flags defined in the
+    /// struct implementing this trait, for the program named `bin_name`.
+    ///
+    /// See [`ctflag::completion_script()`] for how flag values are completed.
+    /// The script can be generated by the program itself, or written out by
+    /// a build script:
+    ///
+    /// ```no_run
+    /// # use ctflag::{DescribeFlags, Flags, Shell};
+    /// # #[derive(Flags)]
+    /// # struct MyFlags {
+    /// #     enable_floopy: bool,
+    /// # }
+    /// # fn main() -> std::io::Result<()> {
+    /// let out_dir = std::env::var("OUT_DIR").unwrap();
+    /// std::fs::write(
+    ///     format!("{}/prog.bash", out_dir),
+    ///     MyFlags::completion_script(Shell::Bash, "prog"),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::completion_script()`]: fn.completion_script.html
+    fn completion_script(shell: Shell, bin_name: &str) -> String {
+        completion::completion_script(shell, bin_name, &Self::metadata())
+    }
+
+    /// Prints the candidates for completing a partial command-line and
+    /// exits, if the program was run by a completion script to complete the
+    /// value of a flag with a `#[flag(complete = ...)]` function.
+    ///
+    /// See [`ctflag::handle_completion()`].
+    ///
+    /// ```no_run
+    /// # use ctflag::{DescribeFlags, Flags};
+    /// # #[derive(Flags)]
+    /// # struct MyFlags {
+    /// #     enable_floopy: bool,
+    /// # }
+    /// # fn main() -> ctflag::Result<()> {
+    /// MyFlags::handle_completion();
+    /// let (flags, args) = MyFlags::from_args(std::env::args())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::handle_completion()`]: fn.handle_completion.html
+    fn handle_completion() {
+        completion::handle_completion(&Self::metadata())
+    }
+
+    /// Returns a man page for the program named `bin_name`, in roff format,
+    /// describing the flags defined in the struct implementing this trait.
+    /// The doc comment of the struct is the description of the program.
+    ///
+    /// See [`ctflag::man_page()`].  The man page can be written out by a
+    /// build script or a test:
+    ///
+    /// ```no_run
+    /// # use ctflag::{DescribeFlags, Flags};
+    /// /// Floops the whoop.
+    /// ##[derive(Flags)]
+    /// struct MyFlags {
+    ///     #[flag(desc = "The floopy floops the whoop")]
+    ///     enable_floopy: bool,
+    /// }
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// std::fs::write("prog.1", MyFlags::man_page("prog"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::man_page()`]: fn.man_page.html
+    fn man_page(bin_name: &str) -> String {
+        man::man_page(
+            bin_name,
+            Self::about(),
+            &Self::metadata(),
+            &Self::groups(),
+        )
+    }
+
+    /// Returns a reference page in Markdown for the program named
+    /// `bin_name`, with a table of the flags defined in the struct
+    /// implementing this trait.  The doc comment of the struct is the
+    /// description of the program.
+    ///
+    /// See [`ctflag::markdown_reference()`].  A test can check that a
+    /// checked-in reference is up to date:
+    ///
+    /// ```no_run
+    /// # use ctflag::{DescribeFlags, Flags};
+    /// # #[derive(Flags)]
+    /// # struct MyFlags {
+    /// #     enable_floopy: bool,
+    /// # }
+    /// # fn main() -> std::io::Result<()> {
+    /// assert_eq!(
+    ///     MyFlags::markdown_reference("prog"),
+    ///     std::fs::read_to_string("docs/prog.md")?,
+    ///     "docs/prog.md is out of date"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ctflag::markdown_reference()`]: fn.markdown_reference.html
+    fn markdown_reference(bin_name: &str) -> String {
+        reference::markdown_reference(
+            bin_name,
+            Self::about(),
+            &Self::metadata(),
+            &Self::groups(),
+        )
+    }
+
+    /// Returns the same reference page as
+    /// [`ctflag::DescribeFlags::markdown_reference()`], as an HTML fragment.
+    ///
+    /// [`ctflag::DescribeFlags::markdown_reference()`]: trait.DescribeFlags.html#method.markdown_reference
+    fn html_reference(bin_name: &str) -> String {
+        reference::html_reference(
+            bin_name,
+            Self::about(),
+            &Self::metadata(),
+            &Self::groups(),
+        )
+    }
+
+    /// Returns a JSON Schema describing the flags defined in the struct
+    /// implementing this trait, with a property for each flag.  External
+    /// tools can use it to check a configuration before running the program
+    /// named `bin_name`.
+    ///
+    /// See [`ctflag::json_schema()`] for how flags are described.
+    ///
+    /// [`ctflag::json_schema()`]: fn.json_schema.html
+    fn json_schema(bin_name: &str) -> String {
+        schema::json_schema(bin_name, Self::about(), &Self::metadata())
+    }
+}
+
+/// Parses flags from borrowed arguments, without copying them, for programs
+/// that parse many command-lines, like a REPL.  Implemented by
+/// `#[derive(Flags)]`.
+///
+/// Fields of type `&str` and `Option<&str>` borrow their value from the
+/// arguments.  The struct must then declare a lifetime parameter for them,
+/// and only implements this trait, since [`ctflag::Flags`] parses arguments
+/// that it owns.  It still describes its flags with
+/// [`ctflag::DescribeFlags`].
+///
+/// ```
+/// # use ctflag::{BorrowedFlags, DescribeFlags, Flags};
+/// ##[derive(Flags)]
+/// struct Command<'a> {
+///     #[flag(choices = ["get", "put"])]
+///     op: &'a str,
+///     key: Option<&'a str>,
+///     retries: u32,
+/// }
+///
+/// # fn main() -> ctflag::Result<()> {
+/// let line = "cmd --op=put --key k1 --retries=3 value";
+/// let (command, args) = Command::from_str_args(line.split(' '))?;
+/// assert_eq!(command.op, "put");
+/// assert_eq!(command.key, Some("k1"));
+/// assert_eq!(command.retries, 3);
+/// assert_eq!(args, ["cmd", "value"]);
+/// assert!(Command::description().contains("--op VALUE"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ctflag::Flags`]: trait.Flags.html
+/// [`ctflag::DescribeFlags`]: trait.DescribeFlags.html
+pub trait BorrowedFlags<'a>: DescribeFlags {
+    /// Like [`ctflag::Flags::from_args()`], but borrows the arguments.  The
+    /// arguments that were not consumed are returned as they were given.
+    ///
+    /// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+    fn from_str_args<T>(args: T) -> Result<(Self, Vec<&'a str>)>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        Self::parse_str_args(args, true).map_err(internal::first_error)
+    }
+
+    /// Like [`ctflag::Flags::from_args_all()`], but borrows the arguments.
+    ///
+    /// [`ctflag::Flags::from_args_all()`]: trait.Flags.html#method.from_args_all
+    fn from_str_args_all<T>(
+        args: T,
+    ) -> std::result::Result<(Self, Vec<&'a str>), FlagErrors>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        Self::parse_str_args(args, false)
+    }
+
+    // Parses the arguments, stopping at the first error if `fail_fast` is
+    // set.
+    #[doc(hidden)]
+    fn parse_str_args<T>(
+        args: T,
+        fail_fast: bool,
+    ) -> std::result::Result<(Self, Vec<&'a str>), FlagErrors>
+    where
+        T: IntoIterator<Item = &'a str>;
+}
+
+/// Converts a value of a struct deriving [`ctflag::Flags`] back into
+/// command-line arguments.  Derived with `#[derive(ToArgs)]` alongside
+/// [`ctflag::Flags`], for logging or forwarding the flags a program was
+/// run with.
+///
+/// Each flag is written as `--name=value`, with the value converted by
+/// [`ctflag::ToArg`].  Optional flags that are unset are left out.  The
+/// arguments don't include a program name, which
+/// [`ctflag::Flags::from_args()`] expects first.
+///
+/// ```
+/// # use ctflag::{Flags, ToArgs};
+/// ##[derive(Flags, ToArgs)]
+/// struct MyFlags {
+///     enable_floopy: bool,
+///     output: Option<String>,
+///     #[flag(default = 34)]
+///     slomps: i64,
+/// }
+///
+/// # fn main() -> ctflag::Result<()> {
+/// let args = vec!["prog", "--output", "out.txt", "--slomps", "10"];
+/// let (flags, _) =
+///     MyFlags::from_args(args.into_iter().map(String::from))?;
+/// assert_eq!(flags.to_args(), vec!["--output=out.txt", "--slomps=10"]);
+/// assert_eq!(
+///     flags.to_all_args(),
+///     vec!["--enable_floopy=false", "--output=out.txt", "--slomps=10"]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ctflag::Flags`]: trait.Flags.html
+/// [`ctflag::ToArg`]: trait.ToArg.html
+/// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+pub trait ToArgs: DescribeFlags {
+    /// Returns the flags whose values differ from their defaults, as
+    /// command-line arguments.
+    fn to_args(&self) -> Vec<String>;
+
+    /// Returns every flag as a command-line argument, in declaration order.
+    fn to_all_args(&self) -> Vec<String>;
 }
 
 #[derive(Clone, Debug)]
 pub struct FromArgError {
     msg: Option<String>,
+    source: Option<Arc<dyn Error + Send + Sync>>,
 }
 
 pub type FromArgResult<T> = std::result::Result<T, FromArgError>;
 
 /// Any type declared in a struct that derives [`ctflag::Flags`] must implement
-/// this trait.  A blanket implementation exists for types implementing `FromStr`.
-/// Custom types can implement this trait directly.
+/// this trait.  A blanket implementation exists for types implementing `FromStr`
+/// with an error that implements `std::error::Error`, which is kept as the
+/// source of the [`ctflag::FromArgError`].  Other types, including those whose
+/// `FromStr` error is not an `Error`, can implement this trait directly, and
+/// enums without fields can derive it with `#[derive(FromArg)]`.
 ///
 /// [`ctflag::Flags`]: trait.Flags.html
+/// [`ctflag::FromArgError`]: struct.FromArgError.html
 ///
 /// ```
 /// # use ctflag::{Flags, FromArg, FromArgError, FromArgResult};
@@ -287,62 +997,503 @@ pub trait FromArg: Sized {
     /// an `Err`.
     /// [`ctflag::FromArgError::with_message`]: struct.FromArgError.html#method.with_message
     fn from_arg(value: &str) -> FromArgResult<Self>;
+
+    /// Returns the values accepted by [`ctflag::FromArg::from_arg`], if
+    /// there is a fixed set of them.  Used to generate shell completions.
+    ///
+    /// [`ctflag::FromArg::from_arg`]: trait.FromArg.html#tymethod.from_arg
+    fn possible_values() -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl<T> FromArg for T
 where
     T: FromStr,
+    <T as FromStr>::Err: Error + Send + Sync + 'static,
 {
     fn from_arg(s: &str) -> FromArgResult<T> {
-        <T as FromStr>::from_str(s).map_err(|_| FromArgError::new())
+        <T as FromStr>::from_str(s).map_err(FromArgError::with_source)
     }
 }
 
-impl fmt::Display for FlagError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            FlagError::ParseError(err) => {
-                write!(
-                    f,
-                    "failed to parse \"{}\" as {} type",
-                    &err.input, err.type_str
-                )?;
-                if let Some(msg) = &err.src.msg {
-                    write!(f, ": {}", msg)?;
-                }
-            }
-            FlagError::MissingValue(arg) => {
-                write!(f, "missing value for argument \"{}\"", arg)?;
-            }
-            FlagError::UnrecognizedArg(arg) => {
-                write!(f, "unrecognized argument \"{}\"", arg)?;
-            }
-        }
-        Ok(())
-    }
+/// Types that can be parsed from the value of a flag that isn't valid UTF-8,
+/// used instead of [`ctflag::FromArg`] by [`ctflag::Flags::from_args_os()`].
+///
+/// Fields of type `PathBuf` and `OsString`, or an `Option` of them, use this
+/// trait automatically.  Fields of other types implementing it must be marked
+/// with `#[flag(os)]`.
+///
+/// ```
+/// # use ctflag::{Flags, FromArgOs, FromArgResult};
+/// # use std::ffi::{OsStr, OsString};
+/// struct Name(OsString);
+///
+/// impl FromArgOs for Name {
+///     fn from_arg_os(value: &OsStr) -> FromArgResult<Self> {
+///         Ok(Name(value.to_os_string()))
+///     }
+/// }
+///
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(os)]
+///     name: Option<Name>,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// [`ctflag::FromArg`]: trait.FromArg.html
+/// [`ctflag::Flags::from_args_os()`]: trait.Flags.html#method.from_args_os
+pub trait FromArgOs: Sized {
+    /// Parses `value` to return the value of this type.
+    fn from_arg_os(value: &OsStr) -> FromArgResult<Self>;
 }
 
-impl FromArgError {
-    fn new() -> Self {
-        FromArgError { msg: None }
+impl FromArgOs for OsString {
+    fn from_arg_os(value: &OsStr) -> FromArgResult<Self> {
+        Ok(value.to_os_string())
     }
+}
 
-    pub fn with_message<T>(msg: T) -> Self
-    where
-        T: fmt::Display,
-    {
-        FromArgError {
-            msg: Some(format!("{}", msg)),
-        }
+impl FromArgOs for PathBuf {
+    fn from_arg_os(value: &OsStr) -> FromArgResult<Self> {
+        Ok(PathBuf::from(value))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    // Since we are inside the ctflag crate, alias crate to ctflag.
+/// The counterpart to [`ctflag::FromArg`], used by [`ctflag::ToArgs`] to
+/// convert a flag's value back into a string.  A blanket implementation
+/// exists for types implementing `Display`.
+///
+/// The string should parse back into the same value with
+/// [`ctflag::FromArg::from_arg`].
+///
+/// [`ctflag::FromArg`]: trait.FromArg.html
+/// [`ctflag::ToArgs`]: trait.ToArgs.html
+/// [`ctflag::FromArg::from_arg`]: trait.FromArg.html#tymethod.from_arg
+pub trait ToArg {
+    /// Returns the value as it would be written on the command-line.
+    fn to_arg(&self) -> String;
+}
+
+impl<T> ToArg for T
+where
+    T: fmt::Display + ?Sized,
+{
+    fn to_arg(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FlagError {
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            FlagError::ParseError(_) => ErrorKind::Parse,
+            FlagError::InvalidUtf8(_) => ErrorKind::InvalidUtf8,
+            FlagError::MissingValue(_) => ErrorKind::MissingValue,
+            FlagError::UnrecognizedArg(_) => ErrorKind::UnrecognizedArg,
+            FlagError::InvalidChoice(_) => ErrorKind::InvalidChoice,
+            FlagError::OutOfRange(_) => ErrorKind::OutOfRange,
+            FlagError::Validation(_) => ErrorKind::Validation,
+            FlagError::Conflict(_) => ErrorKind::Conflict,
+            FlagError::Requires(_) => ErrorKind::Requires,
+            FlagError::MissingOneOf(_) => ErrorKind::MissingOneOf,
+            FlagError::MissingRequired(_) => ErrorKind::MissingRequired,
+        }
+    }
+
+    /// Returns the name of the flag the error is about, without dashes.
+    /// Unrecognized flags are returned as they were written.  Returns `None`
+    /// if the error is not about a single flag.
+    pub fn flag(&self) -> Option<&str> {
+        match self {
+            FlagError::ParseError(err) => Some(&err.flag),
+            FlagError::InvalidUtf8(err) => Some(&err.flag),
+            FlagError::MissingValue(err) => Some(&err.flag),
+            FlagError::UnrecognizedArg(err) => Some(&err.arg),
+            FlagError::InvalidChoice(err) => Some(&err.flag),
+            FlagError::OutOfRange(err) => Some(&err.flag),
+            FlagError::Validation(err) => err.flag.as_deref(),
+            FlagError::Conflict(err) => Some(&err.flag),
+            FlagError::Requires(err) => Some(&err.flag),
+            FlagError::MissingOneOf(_) => None,
+            FlagError::MissingRequired(err) => Some(&err.flag),
+        }
+    }
+
+    /// Returns the index in argv of the flag the error is about, counting the
+    /// program name as 0.  Returns `None` if the flag wasn't set, like a
+    /// missing required flag.
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            FlagError::ParseError(err) => err.index,
+            FlagError::InvalidUtf8(err) => err.index,
+            FlagError::MissingValue(err) => err.index,
+            FlagError::UnrecognizedArg(err) => err.index,
+            FlagError::InvalidChoice(err) => err.index,
+            FlagError::OutOfRange(err) => err.index,
+            FlagError::Validation(err) => err.index,
+            FlagError::Conflict(err) => err.index,
+            FlagError::Requires(err) => err.index,
+            FlagError::MissingOneOf(_) | FlagError::MissingRequired(_) => None,
+        }
+    }
+
+    /// Returns a value that displays this error the same way as its `Display`
+    /// implementation, with flag names and the offending input highlighted if
+    /// color is enabled.  [`ctflag::ColorChoice::Auto`] highlights the error
+    /// if stderr is a terminal.
+    ///
+    /// ```
+    /// # use ctflag::{ColorChoice, Flags};
+    /// # #[derive(Flags)]
+    /// # struct MyFlags {
+    /// #     enable_floopy: bool,
+    /// # }
+    /// if let Err(err) = MyFlags::from_args(std::env::args()) {
+    ///     eprintln!("{}", err.styled(ColorChoice::Auto));
+    /// }
+    /// ```
+    ///
+    /// [`ctflag::ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+    pub fn styled(&self, color: ColorChoice) -> StyledFlagError<'_> {
+        StyledFlagError {
+            err: self,
+            style: Style::new(color.should_color(Stream::Stderr)),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, style: Style) -> fmt::Result {
+        match self {
+            FlagError::ParseError(err) => {
+                write!(
+                    f,
+                    "failed to parse \"{}\" as {} type for argument \"{}\"",
+                    style.invalid(&err.input),
+                    err.type_str,
+                    style.literal(&err.flag)
+                )?;
+                if let Some(msg) = &err.src.msg {
+                    write!(f, ": {}", msg)?;
+                }
+            }
+            FlagError::InvalidUtf8(err) => {
+                write!(
+                    f,
+                    "invalid UTF-8 in value \"{}\" for argument \"{}\"",
+                    style.invalid(&err.input.to_string_lossy()),
+                    style.literal(&err.flag)
+                )?;
+            }
+            FlagError::MissingValue(err) => {
+                write!(
+                    f,
+                    "missing value for argument \"{}\"",
+                    style.literal(&err.flag)
+                )?;
+            }
+            FlagError::UnrecognizedArg(err) => {
+                write!(
+                    f,
+                    "unrecognized argument \"{}\"",
+                    style.invalid(&err.arg)
+                )?;
+                if let Some(suggestion) = &err.suggestion {
+                    write!(
+                        f,
+                        "; did you mean \"{}\"?",
+                        style.literal(suggestion)
+                    )?;
+                }
+            }
+            FlagError::InvalidChoice(err) => {
+                write!(
+                    f,
+                    "invalid value \"{}\" for argument \"{}\", expected one of: {}",
+                    style.invalid(&err.input),
+                    style.literal(&err.flag),
+                    err.choices.join(", ")
+                )?;
+                if let Some(suggestion) = err.suggestion {
+                    write!(
+                        f,
+                        "; did you mean \"{}\"?",
+                        style.literal(suggestion)
+                    )?;
+                }
+            }
+            FlagError::OutOfRange(err) => {
+                write!(
+                    f,
+                    "invalid value \"{}\" for argument \"{}\", expected ",
+                    style.invalid(&err.value),
+                    style.literal(&err.flag)
+                )?;
+                match (&err.min, &err.max) {
+                    (Some(min), Some(max)) => {
+                        write!(f, "a value from {} to {}", min, max)?
+                    }
+                    (Some(min), None) => {
+                        write!(f, "a value of at least {}", min)?
+                    }
+                    (None, Some(max)) => {
+                        write!(f, "a value of at most {}", max)?
+                    }
+                    (None, None) => write!(f, "a value in range")?,
+                }
+            }
+            FlagError::Validation(err) => match &err.flag {
+                Some(flag) => write!(
+                    f,
+                    "invalid value for argument \"{}\": {}",
+                    style.literal(flag),
+                    err.msg
+                )?,
+                None => write!(f, "invalid arguments: {}", err.msg)?,
+            },
+            FlagError::Conflict(err) => {
+                write!(
+                    f,
+                    "argument \"{}\" cannot be used with \"{}\"",
+                    style.literal(&err.flag),
+                    style.literal(&err.other)
+                )?;
+            }
+            FlagError::Requires(err) => {
+                write!(
+                    f,
+                    "argument \"{}\" requires \"{}\"",
+                    style.literal(&err.flag),
+                    style.literal(&err.required)
+                )?;
+            }
+            FlagError::MissingOneOf(flags) => {
+                let flags = flags
+                    .iter()
+                    .map(|flag| format!("\"{}\"", style.literal(flag)))
+                    .collect::<Vec<String>>();
+                write!(
+                    f,
+                    "one of the arguments {} is required",
+                    flags.join(", ")
+                )?;
+            }
+            FlagError::MissingRequired(err) => {
+                write!(
+                    f,
+                    "missing argument \"{}\", which is required ",
+                    style.literal(&err.flag)
+                )?;
+                match &err.condition {
+                    RequiredCondition::Unless(flags) => {
+                        let flags = flags
+                            .iter()
+                            .map(|flag| format!("\"{}\"", style.literal(flag)))
+                            .collect::<Vec<String>>();
+                        write!(f, "unless {} is set", flags.join(" or "))?;
+                    }
+                    RequiredCondition::If { flag, value } => write!(
+                        f,
+                        "when \"{}\" is \"{}\"",
+                        style.literal(flag),
+                        style.literal(value)
+                    )?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FlagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, Style::plain())
+    }
+}
+
+impl Error for FlagError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FlagError::ParseError(err) => Some(&err.src),
+            _ => None,
+        }
+    }
+}
+
+impl FlagErrors {
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    // Never true, but expected alongside `len()`.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, FlagError> {
+        self.errors.iter()
+    }
+
+    /// Returns the error that was found first.
+    pub fn first(&self) -> &FlagError {
+        &self.errors[0]
+    }
+
+    /// Returns a value that displays these errors the same way as their
+    /// `Display` implementation, styled like
+    /// [`ctflag::FlagError::styled()`].
+    ///
+    /// [`ctflag::FlagError::styled()`]: enum.FlagError.html#method.styled
+    pub fn styled(&self, color: ColorChoice) -> StyledFlagErrors<'_> {
+        StyledFlagErrors {
+            errors: self,
+            style: Style::new(color.should_color(Stream::Stderr)),
+        }
+    }
+
+    // Writes each error on its own line.
+    fn write(&self, f: &mut fmt::Formatter, style: Style) -> fmt::Result {
+        for (i, err) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            err.write(f, style)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<FlagError> for FlagErrors {
+    fn from(err: FlagError) -> Self {
+        FlagErrors { errors: vec![err] }
+    }
+}
+
+impl IntoIterator for FlagErrors {
+    type Item = FlagError;
+    type IntoIter = std::vec::IntoIter<FlagError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FlagErrors {
+    type Item = &'a FlagError;
+    type IntoIter = std::slice::Iter<'a, FlagError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+impl fmt::Display for FlagErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, Style::plain())
+    }
+}
+
+impl Error for FlagErrors {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.first())
+    }
+}
+
+/// Displays [`ctflag::FlagErrors`] with ANSI styles.  Returned by
+/// [`ctflag::FlagErrors::styled()`].
+///
+/// [`ctflag::FlagErrors`]: struct.FlagErrors.html
+/// [`ctflag::FlagErrors::styled()`]: struct.FlagErrors.html#method.styled
+pub struct StyledFlagErrors<'a> {
+    errors: &'a FlagErrors,
+    style: Style,
+}
+
+impl<'a> fmt::Display for StyledFlagErrors<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.errors.write(f, self.style)
+    }
+}
+
+/// Displays a [`ctflag::FlagError`] with ANSI styles.  Returned by
+/// [`ctflag::FlagError::styled()`].
+///
+/// [`ctflag::FlagError`]: enum.FlagError.html
+/// [`ctflag::FlagError::styled()`]: enum.FlagError.html#method.styled
+pub struct StyledFlagError<'a> {
+    err: &'a FlagError,
+    style: Style,
+}
+
+impl<'a> fmt::Display for StyledFlagError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.err.write(f, self.style)
+    }
+}
+
+impl FromArgError {
+    pub fn with_message<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        FromArgError {
+            msg: Some(format!("{}", msg)),
+            source: None,
+        }
+    }
+
+    /// Returns an error with the message of `err`, which is kept as the
+    /// source of the error.
+    pub fn with_source<E>(err: E) -> Self
+    where
+        E: Error + Send + Sync + 'static,
+    {
+        FromArgError {
+            msg: Some(err.to_string()),
+            source: Some(Arc::new(err)),
+        }
+    }
+
+    /// Returns the message describing why the value was rejected, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.msg.as_deref()
+    }
+}
+
+impl fmt::Display for FromArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.msg {
+            Some(msg) => write!(f, "{}", msg),
+            None => write!(f, "invalid value"),
+        }
+    }
+}
+
+impl Error for FromArgError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(err) => Some(&**err),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // Since we are inside the ctflag crate, alias crate to ctflag.
     use crate as ctflag;
 
+    // Parses `args`, which leave out the program name, into `F`.
+    fn parse<F: Flags>(args: &[&str]) -> Result<F> {
+        let args = std::iter::once("prog")
+            .chain(args.iter().cloned())
+            .map(String::from);
+        F::from_args(args).map(|(flags, _)| flags)
+    }
+
     #[derive(Flags)]
     struct Simple {
         one: String,
@@ -374,7 +1525,19 @@ mod tests {
         }
     }
 
+    #[derive(Debug, PartialEq, Flags, ToArgs)]
+    struct RoundTrip {
+        name: String,
+        #[flag(default = 8080)]
+        port: i32,
+        verbose: bool,
+        ratio: Option<f64>,
+        #[flag(default = "fast")]
+        mode: String,
+    }
+
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_defaults() {
         let args = vec![String::from("prog_name")];
         let (flags, rest) = Simple::from_args(args).unwrap();
@@ -408,6 +1571,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_bool_using_eq() {
         let args =
             vec![String::from("prog_name"), String::from("--three=true")];
@@ -416,6 +1580,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_bool_using_space() {
         let args = vec![
             String::from("prog_name"),
@@ -550,11 +1715,27 @@ mod tests {
         one: CustomType,
     }
 
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    struct BadEnumDefault {
+        #[flag(default = "ftp")]
+        protocol: Protocol,
+    }
+
     #[test]
-    #[should_panic]
     fn test_bad_default() {
         let args = vec![String::from("prog_name")];
-        let _result = BadDefault::from_args(args);
+        assert_matches!(
+            BadDefault::from_args(args).err().unwrap(),
+            FlagError::ParseError(err),
+            err.flag == "one" && err.input == "bad"
+        );
+        let args = vec![String::from("prog_name")];
+        assert_matches!(
+            BadEnumDefault::from_args(args).err().unwrap(),
+            FlagError::ParseError(err),
+            err.flag == "protocol" && err.input == "ftp"
+        );
     }
 
     #[allow(dead_code)]
@@ -576,6 +1757,85 @@ mod tests {
         assert!(desc.contains("-t, --two [VROOM]    Boom"));
     }
 
+    #[test]
+    fn test_styled_error() {
+        let args =
+            vec![String::from("prog_name"), String::from("--five=hello")];
+        let err = Simple::from_args(args).err().unwrap();
+        assert_eq!(
+            format!("{}", err.styled(ColorChoice::Never)),
+            format!("{}", err)
+        );
+        assert_eq!(
+            format!("{}", err.styled(ColorChoice::Always)),
+            "failed to parse \"\x1b[1;33mhello\x1b[0m\" as i32 type for \
+             argument \"\x1b[1mfive\x1b[0m\": invalid digit found in string"
+        );
+    }
+
+    /// Connects to things.
+    ///
+    /// In groups.
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    #[flags(group(name = "Networking", desc = "Connection settings"))]
+    struct Grouped {
+        #[flag(desc = "Server address", group = "Networking")]
+        address: String,
+
+        #[flag(desc = "Prints this help message")]
+        help: bool,
+
+        #[flag(short = 'p', desc = "Server port", group = "Networking")]
+        port: i32,
+    }
+
+    #[test]
+    fn test_description_groups() {
+        assert_eq!(
+            Grouped::description(),
+            "OPTIONS:\n\
+             \x20     --help    Prints this help message\n\
+             \n\
+             Networking:\n\
+             \x20 Connection settings\n\
+             \x20     --address VALUE    Server address\n\
+             \x20 -p, --port VALUE       Server port\n"
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let flags = Description::metadata();
+        assert_eq!(flags.len(), 2);
+        assert_eq!(
+            flags[0],
+            ctflag::FlagInfo::new("one", "String", ctflag::FlagType::String)
+                .placeholder("THING")
+                .description("Howdy")
+                .default("foo")
+        );
+        assert_eq!(
+            flags[1],
+            ctflag::FlagInfo::new("two", "Option<i32>", ctflag::FlagType::Int)
+                .short('t')
+                .placeholder("VROOM")
+                .description("Boom")
+                .optional(true)
+        );
+        assert_eq!(Simple::metadata()[2].placeholder, None);
+        assert_eq!(Grouped::metadata()[0].group, Some("Networking"));
+        assert_eq!(
+            Grouped::groups(),
+            vec![ctflag::GroupInfo {
+                name: "Networking",
+                description: Some("Connection settings"),
+            }]
+        );
+        assert_eq!(Grouped::about(), Some("Connects to things.\n\nIn groups."));
+        assert_eq!(Simple::about(), None);
+    }
+
     #[derive(Flags)]
     struct ShortFlag {
         #[flag(short = 'o')]
@@ -599,4 +1859,808 @@ mod tests {
         let (flags, _rest) = ShortFlag::from_args(args).unwrap();
         assert_eq!(flags.output, "file");
     }
+
+    #[test]
+    fn test_to_args() {
+        let flags = RoundTrip {
+            name: String::from("a b=c"),
+            port: 8080,
+            verbose: true,
+            ratio: Some(0.1),
+            mode: String::from("fast"),
+        };
+        assert_eq!(
+            flags.to_args(),
+            vec!["--name=a b=c", "--verbose=true", "--ratio=0.1"]
+        );
+        assert_eq!(
+            flags.to_all_args(),
+            vec![
+                "--name=a b=c",
+                "--port=8080",
+                "--verbose=true",
+                "--ratio=0.1",
+                "--mode=fast"
+            ]
+        );
+        for args in [flags.to_args(), flags.to_all_args()] {
+            let args = std::iter::once(String::from("prog")).chain(args);
+            let (parsed, rest) = RoundTrip::from_args(args).unwrap();
+            assert_eq!(parsed, flags);
+            assert_eq!(rest, vec!["prog"]);
+        }
+    }
+
+    #[test]
+    fn test_to_args_defaults() {
+        let (flags, _) =
+            RoundTrip::from_args(vec![String::from("prog")]).unwrap();
+        assert!(flags.to_args().is_empty());
+        assert_eq!(flags.to_all_args().len(), 4);
+    }
+
+    #[derive(Debug, PartialEq, FromArg)]
+    enum Protocol {
+        Http,
+        #[arg(alias = "ws")]
+        WebSocket,
+        #[arg(rename = "grpc", alias = "rpc", alias = "g")]
+        GRPCWeb,
+        HTTPProxy,
+    }
+
+    #[derive(Debug, PartialEq, FromArg)]
+    #[arg(ignore_case)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    // Deriving `FromArg` leaves `Display`, and so `ToArg`, to the enum.
+    impl fmt::Display for Level {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match self {
+                Level::Low => "low",
+                Level::High => "high",
+            })
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    struct WithEnums {
+        #[flag(desc = "Wire protocol", default = "http")]
+        protocol: Protocol,
+        level: Option<Level>,
+    }
+
+    #[test]
+    fn test_derive_from_arg() {
+        assert_eq!(Protocol::from_arg("http").unwrap(), Protocol::Http);
+        assert_eq!(
+            Protocol::from_arg("web-socket").unwrap(),
+            Protocol::WebSocket
+        );
+        assert_eq!(Protocol::from_arg("ws").unwrap(), Protocol::WebSocket);
+        assert_eq!(Protocol::from_arg("g").unwrap(), Protocol::GRPCWeb);
+        assert_eq!(
+            Protocol::from_arg("http-proxy").unwrap(),
+            Protocol::HTTPProxy
+        );
+        assert!(Protocol::from_arg("HTTP").is_err());
+        assert_eq!(Level::from_arg("HiGh").unwrap(), Level::High);
+        assert_eq!(
+            Protocol::possible_values(),
+            &["http", "web-socket", "grpc", "http-proxy"]
+        );
+        assert_eq!(Level::Low.to_arg(), "low");
+        assert_eq!(
+            Level::from_arg(&Level::High.to_arg()).unwrap(),
+            Level::High
+        );
+    }
+
+    #[test]
+    fn test_derive_from_arg_error() {
+        let args = vec![String::from("prog"), String::from("--protocol=ftp")];
+        let err = WithEnums::from_args(args).err().unwrap();
+        assert_eq!(
+            format!("{}", err),
+            "failed to parse \"ftp\" as Protocol type for argument \
+             \"protocol\": expected one of: http, web-socket, grpc, http-proxy"
+        );
+    }
+
+    #[test]
+    fn test_description_possible_values() {
+        let desc = WithEnums::description();
+        assert!(desc.contains(
+            "--protocol VALUE    Wire protocol (defaults to \"http\") \
+             [possible values: http, web-socket, grpc, http-proxy]"
+        ));
+        assert!(
+            desc.contains("--level [VALUE]     [possible values: low, high]")
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    struct WithChoices {
+        #[flag(choices = ["fast", "safe", "debug"], default = "safe")]
+        mode: String,
+        #[flag(choices = ["x86", "arm"])]
+        arch: Option<String>,
+    }
+
+    #[test]
+    fn test_choices() {
+        let args = vec![
+            String::from("prog"),
+            String::from("--mode=debug"),
+            String::from("--arch"),
+            String::from("arm"),
+        ];
+        let (flags, _) = WithChoices::from_args(args).unwrap();
+        assert_eq!(flags.mode, "debug");
+        assert_eq!(flags.arch.as_deref(), Some("arm"));
+
+        let args = vec![String::from("prog"), String::from("--mode=fsat")];
+        let err = WithChoices::from_args(args).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::InvalidChoice(err),
+            err.flag == "mode" && err.suggestion == Some("fast")
+        );
+        assert_eq!(
+            format!("{}", err),
+            "invalid value \"fsat\" for argument \"mode\", \
+             expected one of: fast, safe, debug; did you mean \"fast\"?"
+        );
+
+        let args = vec![String::from("prog"), String::from("--arch=mips")];
+        assert_eq!(
+            format!("{}", WithChoices::from_args(args).err().unwrap()),
+            "invalid value \"mips\" for argument \"arch\", \
+             expected one of: x86, arm"
+        );
+    }
+
+    #[test]
+    fn test_choices_metadata() {
+        let flags = WithChoices::metadata();
+        assert_eq!(flags[0].possible_values, &["fast", "safe", "debug"]);
+        assert!(WithChoices::description().contains(
+            "--mode VALUE       (defaults to \"safe\") \
+             [possible values: fast, safe, debug]"
+        ));
+        assert!(WithChoices::completion_script(Shell::Bash, "prog")
+            .contains("compgen -W \"x86 arm\""));
+    }
+
+    #[test]
+    fn test_derive_from_arg_suggestion() {
+        let err = Protocol::from_arg("web-sokcet").err().unwrap();
+        assert_eq!(
+            err.msg.as_deref(),
+            Some(
+                "expected one of: http, web-socket, grpc, http-proxy; \
+                 did you mean \"web-socket\"?"
+            )
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    struct WithRanges {
+        #[flag(range = 1..=256, default = 4)]
+        threads: u32,
+        #[flag(min = -1, max = 1)]
+        bias: Option<f64>,
+        #[flag(range = 0.5..)]
+        ratio: f32,
+    }
+
+    #[test]
+    fn test_ranges() {
+        let flags =
+            parse::<WithRanges>(&["--threads=256", "--bias=-1", "--ratio=0.5"])
+                .unwrap();
+        assert_eq!(flags.threads, 256);
+        assert_eq!(flags.bias, Some(-1.0));
+        assert_eq!(parse::<WithRanges>(&[]).unwrap().threads, 4);
+
+        let err = parse::<WithRanges>(&["--threads", "0"]).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::OutOfRange(err),
+            err.flag == "threads"
+                && err.min.as_deref() == Some("1")
+                && err.max.as_deref() == Some("256")
+        );
+        assert_eq!(
+            format!("{}", err),
+            "invalid value \"0\" for argument \"threads\", \
+             expected a value from 1 to 256"
+        );
+        assert_eq!(
+            format!("{}", parse::<WithRanges>(&["--bias=1.5"]).err().unwrap()),
+            "invalid value \"1.5\" for argument \"bias\", \
+             expected a value from -1 to 1"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                parse::<WithRanges>(&["--ratio=0.25"]).err().unwrap()
+            ),
+            "invalid value \"0.25\" for argument \"ratio\", \
+             expected a value of at least 0.5"
+        );
+    }
+
+    #[test]
+    fn test_ranges_nan() {
+        assert_matches!(
+            parse::<WithRanges>(&["--bias=NaN"]).err().unwrap(),
+            FlagError::OutOfRange(err),
+            err.flag == "bias" && err.value == "NaN"
+        );
+        assert_matches!(
+            parse::<WithRanges>(&["--ratio=NaN"]).err().unwrap(),
+            FlagError::OutOfRange(err),
+            err.flag == "ratio"
+        );
+    }
+
+    #[test]
+    fn test_ranges_metadata() {
+        let flags = WithRanges::metadata();
+        assert_eq!((flags[1].min, flags[1].max), (Some("-1"), Some("1")));
+        assert_eq!((flags[2].min, flags[2].max), (Some("0.5"), None));
+        let desc = WithRanges::description();
+        assert!(desc.contains(
+            "--threads VALUE     (defaults to 4) [min: 1, max: 256]"
+        ));
+        assert!(desc.contains("--ratio VALUE      [min: 0.5]"));
+        let schema = WithRanges::json_schema("prog");
+        assert!(schema.contains("\"minimum\": 1,\n"));
+        assert!(schema.contains("\"maximum\": 256\n"));
+    }
+
+    fn check_port(port: &u16) -> std::result::Result<(), String> {
+        if *port < 1024 {
+            return Err(String::from("ports below 1024 are reserved"));
+        }
+        Ok(())
+    }
+
+    fn check_retries(retries: &u32) -> std::result::Result<(), String> {
+        if *retries > 10 {
+            return Err(String::from("too many retries"));
+        }
+        Ok(())
+    }
+
+    fn check_validated(flags: &Validated) -> std::result::Result<(), String> {
+        if flags.quiet && flags.retries.is_some() {
+            return Err(String::from("--quiet does not report retries"));
+        }
+        Ok(())
+    }
+
+    #[derive(Flags)]
+    #[flags(validate = check_validated)]
+    struct Validated {
+        #[flag(validate = check_port, default = 8080)]
+        port: u16,
+        #[flag(validate = check_retries)]
+        retries: Option<u32>,
+        quiet: bool,
+    }
+
+    #[test]
+    fn test_validate() {
+        let flags =
+            parse::<Validated>(&["--port=2000", "--retries=3"]).unwrap();
+        assert_eq!(flags.port, 2000);
+        assert_eq!(flags.retries, Some(3));
+        assert_eq!(parse::<Validated>(&["--quiet"]).unwrap().port, 8080);
+
+        let err = parse::<Validated>(&["--port", "80"]).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::Validation(err),
+            err.flag.as_deref() == Some("port")
+        );
+        assert_eq!(
+            format!("{}", err),
+            "invalid value for argument \"port\": \
+             ports below 1024 are reserved"
+        );
+        assert_matches!(
+            parse::<Validated>(&["--retries=11"]).err().unwrap(),
+            FlagError::Validation(err),
+            err.flag.as_deref() == Some("retries")
+        );
+    }
+
+    #[derive(Flags)]
+    struct ValidatedDefault {
+        #[flag(validate = check_port)]
+        port: u16,
+    }
+
+    #[test]
+    fn test_validate_default() {
+        let args = ["prog"].iter().map(|arg| String::from(*arg));
+        assert_matches!(
+            ValidatedDefault::from_args(args).err().unwrap(),
+            FlagError::Validation(err),
+            err.flag.as_deref() == Some("port") && err.index.is_none()
+        );
+        let args = ["prog", "--port=2000"].iter().map(|arg| String::from(*arg));
+        assert_eq!(ValidatedDefault::from_args(args).unwrap().0.port, 2000);
+    }
+
+    #[test]
+    fn test_validate_flags() {
+        let err = parse::<Validated>(&["--quiet", "--retries=1"])
+            .err()
+            .unwrap();
+        assert_matches!(&err, FlagError::Validation(err), err.flag.is_none());
+        assert_eq!(
+            format!("{}", err),
+            "invalid arguments: --quiet does not report retries"
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    #[flags(one_of = ["json", "yaml"])]
+    struct Related {
+        json: bool,
+        yaml: bool,
+        #[flag(requires = "tls_key")]
+        tls_cert: Option<String>,
+        tls_key: Option<String>,
+        #[flag(conflicts_with = ["json", "yaml"])]
+        text: bool,
+    }
+
+    #[test]
+    fn test_relations() {
+        assert!(
+            parse::<Related>(&["--yaml", "--tls_cert=a", "--tls_key=b"])
+                .unwrap()
+                .yaml
+        );
+
+        let err = parse::<Related>(&["--yaml", "--json"]).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::Conflict(err),
+            err.flag == "json" && err.other == "yaml"
+        );
+        assert_eq!(
+            format!("{}", err),
+            "argument \"json\" cannot be used with \"yaml\""
+        );
+        assert_matches!(
+            parse::<Related>(&["--text", "--json"]).err().unwrap(),
+            FlagError::Conflict(err),
+            err.flag == "json" && err.other == "text"
+        );
+
+        assert!(parse::<Related>(&["--json=false", "--yaml"]).unwrap().yaml);
+        assert!(parse::<Related>(&["--json", "--yaml=false"]).unwrap().json);
+        assert!(parse::<Related>(&["--text=false", "--json"]).is_ok());
+        assert!(parse::<Related>(&["--json", "--json=false", "--yaml"]).is_ok());
+
+        let err = parse::<Related>(&["--json", "--tls_cert=a"]).err().unwrap();
+        assert_eq!(
+            format!("{}", err),
+            "argument \"tls_cert\" requires \"tls_key\""
+        );
+
+        let err = parse::<Related>(&[]).err().unwrap();
+        assert_eq!(
+            format!("{}", err),
+            "one of the arguments \"json\", \"yaml\" is required"
+        );
+    }
+
+    #[test]
+    fn test_relations_metadata() {
+        let flags = Related::metadata();
+        assert_eq!(flags[0].conflicts_with, &["yaml", "text"]);
+        assert_eq!(flags[2].requires, &["tls_key"]);
+        assert_eq!(flags[4].conflicts_with, &["json", "yaml"]);
+        let desc = Related::description();
+        assert!(desc.contains("[conflicts with: --yaml, --text]"));
+        assert!(desc.contains("[requires: --tls_key]"));
+    }
+
+    #[allow(dead_code)]
+    #[derive(Flags)]
+    struct Storage {
+        local: bool,
+        #[flag(required_unless = "local")]
+        region: Option<String>,
+        #[flag(choices = ["download", "upload"], default = "download")]
+        mode: String,
+        #[flag(required_if = ("mode", "upload"))]
+        bucket: Option<String>,
+    }
+
+    #[test]
+    fn test_required_unless() {
+        assert!(parse::<Storage>(&["--local"]).is_ok());
+        assert!(parse::<Storage>(&["--region=eu"]).is_ok());
+        assert!(parse::<Storage>(&["--local=false", "--region=eu"]).is_ok());
+        assert_matches!(
+            parse::<Storage>(&["--local=false"]).err().unwrap(),
+            FlagError::MissingRequired(err),
+            err.flag == "region"
+        );
+        let err = parse::<Storage>(&[]).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::MissingRequired(err),
+            err.flag == "region"
+                && matches!(
+                    &err.condition,
+                    RequiredCondition::Unless(flags),
+                    flags == &["local"]
+                )
+        );
+        assert_eq!(
+            format!("{}", err),
+            "missing argument \"region\", which is required unless \
+             \"local\" is set"
+        );
+    }
+
+    #[test]
+    fn test_required_if() {
+        assert!(parse::<Storage>(&["--local", "--mode=download"]).is_ok());
+        assert!(
+            parse::<Storage>(&["--local", "--mode=upload", "--bucket=b"])
+                .is_ok()
+        );
+        let err = parse::<Storage>(&["--local", "--mode=upload"])
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{}", err),
+            "missing argument \"bucket\", which is required when \"mode\" \
+             is \"upload\""
+        );
+        let desc = Storage::description();
+        assert!(desc.contains("--region [VALUE]    [required unless: --local]"));
+        assert!(desc.contains("[required if: --mode=upload]"));
+    }
+
+    #[test]
+    fn test_unrecognized_arg_suggestion() {
+        let args = vec!["prog", "--thre"].into_iter().map(String::from);
+        let err = Simple::from_args(args).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::UnrecognizedArg(err),
+            err.arg == "--thre"
+        );
+        assert_eq!(
+            format!("{}", err),
+            "unrecognized argument \"--thre\"; \
+             did you mean \"--three\"?"
+        );
+
+        let args = vec!["prog", "--unknown"].into_iter().map(String::from);
+        assert_matches!(
+            Simple::from_args(args),
+            Err(FlagError::UnrecognizedArg(err)),
+            err.suggestion.is_none()
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        let args = vec!["prog", "--one", "x", "--five", "hello"]
+            .into_iter()
+            .map(String::from);
+        let err = Simple::from_args(args).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Parse);
+        assert_eq!(err.flag(), Some("five"));
+        assert_eq!(err.index(), Some(3));
+
+        let args = vec!["prog", "--three", "--two"]
+            .into_iter()
+            .map(String::from);
+        let err = Simple::from_args(args).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::MissingValue);
+        assert_eq!((err.flag(), err.index()), (Some("two"), Some(2)));
+
+        let err = parse::<Related>(&["--json", "--text"]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Conflict);
+        assert_eq!((err.flag(), err.index()), (Some("text"), Some(2)));
+
+        let err = parse::<Storage>(&[]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::MissingRequired);
+        assert_eq!((err.flag(), err.index()), (Some("region"), None));
+    }
+
+    #[test]
+    fn test_error_source() {
+        fn parse() -> std::result::Result<Simple, Box<dyn Error>> {
+            let args =
+                vec!["prog", "--five=hello"].into_iter().map(String::from);
+            Ok(Simple::from_args(args)?.0)
+        }
+        let err = parse().err().unwrap();
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "invalid digit found in string");
+        let source = source.source().unwrap();
+        assert!(source.is::<std::num::ParseIntError>());
+
+        let args = vec!["prog", "--three=maybe"].into_iter().map(String::from);
+        let err = Simple::from_args(args).err().unwrap();
+        let source = err.source().unwrap().source().unwrap();
+        assert!(source.is::<std::str::ParseBoolError>());
+    }
+
+    #[test]
+    fn test_from_args_all() {
+        let args = vec!["prog", "--mode=upload", "--nope", "--mode"];
+        let args = args.into_iter().map(String::from);
+        let errors = Storage::from_args_all(args).err().unwrap();
+        let kinds = errors.iter().map(FlagError::kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                ErrorKind::UnrecognizedArg,
+                ErrorKind::MissingValue,
+                ErrorKind::MissingRequired,
+                ErrorKind::MissingRequired,
+            ]
+        );
+        assert_eq!(
+            format!("{}", errors),
+            "unrecognized argument \"--nope\"\n\
+             missing value for argument \"mode\"\n\
+             missing argument \"region\", which is required unless \"local\" \
+             is set\n\
+             missing argument \"bucket\", which is required when \"mode\" is \
+             \"upload\""
+        );
+        assert_eq!(
+            format!("{}", errors.styled(ColorChoice::Never)),
+            format!("{}", errors)
+        );
+
+        let args = vec!["prog", "--local", "--region=eu"];
+        let args = args.into_iter().map(String::from);
+        assert!(Storage::from_args_all(args).is_ok());
+    }
+
+    #[test]
+    fn test_from_args_fails_fast() {
+        let err = parse::<Storage>(&["--nope", "--mode"]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnrecognizedArg);
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Flags)]
+    struct Files {
+        output: Option<PathBuf>,
+        #[flag(short = 'i')]
+        input: std::path::PathBuf,
+        name: String,
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_args_os() {
+        use std::os::unix::ffi::OsStringExt;
+        let raw = |bytes: &[u8]| OsString::from_vec(bytes.to_vec());
+        let args = vec![
+            OsString::from("prog"),
+            raw(b"--output=out\xff"),
+            OsString::from("-i"),
+            raw(b"in\xfe"),
+            raw(b"file\xfd"),
+        ];
+        let (flags, rest) = Files::from_args_os(args).unwrap();
+        assert_eq!(flags.output, Some(PathBuf::from(raw(b"out\xff"))));
+        assert_eq!(flags.input, PathBuf::from(raw(b"in\xfe")));
+        assert_eq!(rest, [OsString::from("prog"), raw(b"file\xfd")]);
+
+        let args = vec![
+            OsString::from("prog"),
+            OsString::from("--name"),
+            raw(b"n\xff"),
+        ];
+        let err = Files::from_args_os(args).err().unwrap();
+        assert_matches!(
+            &err,
+            FlagError::InvalidUtf8(err),
+            err.input == raw(b"n\xff")
+        );
+        assert_eq!(err.kind(), ErrorKind::InvalidUtf8);
+        assert_eq!(err.index(), Some(1));
+        assert_eq!(
+            format!("{}", err),
+            "invalid UTF-8 in value \"n\u{fffd}\" for argument \"name\""
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_paths() {
+        let args = vec!["prog", "--input", "a b", "--name=x", "c"];
+        let args = args.into_iter().map(String::from);
+        let (flags, rest) = Files::from_args(args).unwrap();
+        assert_eq!(flags.input, PathBuf::from("a b"));
+        assert_eq!(flags.output, None);
+        assert_eq!(rest, ["prog", "c"]);
+    }
+
+    /// Runs a command.
+    #[derive(Debug, Flags, ToArgs)]
+    #[flags(group(name = "Output"))]
+    struct Borrowed<'a> {
+        #[flag(short = 'n', default = "anon", desc = "Who runs it")]
+        name: &'a str,
+        #[flag(choices = ["get", "put"])]
+        op: Option<&'a str>,
+        count: i32,
+        #[flag(group = "Output")]
+        verbose: bool,
+    }
+
+    #[test]
+    fn test_from_str_args() {
+        let line = String::from("cmd -n bob --op=put --verbose --count 3 x");
+        let (flags, rest) = Borrowed::from_str_args(line.split(' ')).unwrap();
+        assert_eq!(flags.name, "bob");
+        assert_eq!(flags.op, Some("put"));
+        assert_eq!(flags.count, 3);
+        assert!(flags.verbose);
+        assert_eq!(rest, ["cmd", "x"]);
+
+        let (flags, _) = Borrowed::from_str_args(vec!["cmd"]).unwrap();
+        assert_eq!(flags.name, "anon");
+        assert_eq!(flags.op, None);
+
+        let args = ["cmd", "--op=del", "--count=x"];
+        let errors = Borrowed::from_str_args_all(args.iter().cloned())
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{}", errors),
+            "invalid value \"del\" for argument \"op\", expected one of: \
+             get, put\n\
+             failed to parse \"x\" as i32 type for argument \"count\": \
+             invalid digit found in string"
+        );
+        assert_eq!(errors.first().index(), Some(1));
+    }
+
+    #[test]
+    fn test_borrowed_description() {
+        assert_eq!(Borrowed::about(), Some("Runs a command."));
+        assert_eq!(Borrowed::metadata()[0].name, "name");
+        assert_eq!(Borrowed::groups()[0].name, "Output");
+        let desc = Borrowed::description();
+        assert!(desc.contains(
+            "-n, --name VALUE     Who runs it (defaults to \"anon\")"
+        ));
+        assert!(desc.contains("--op [VALUE]     [possible values: get, put]"));
+        assert!(desc.contains("Output:\n      --verbose"));
+        assert_eq!(HelpRenderer::new().render::<Borrowed>(), desc);
+        assert!(Borrowed::man_page("cmd").contains("Runs a command."));
+    }
+
+    #[test]
+    fn test_borrowed_to_args() {
+        let args = ["cmd", "-n", "bob", "--count=3"];
+        let (flags, _) = Borrowed::from_str_args(args.iter().cloned()).unwrap();
+        assert_eq!(flags.to_args(), ["--name=bob", "--count=3"]);
+    }
+
+    #[test]
+    fn test_owned_flags_from_str_args() {
+        let args = ["prog", "--five", "5", "--two=a.txt", "b"];
+        let (flags, rest) =
+            Simple::from_str_args(args.iter().cloned()).unwrap();
+        assert_eq!(flags.five, 5);
+        assert_eq!(flags.two, Some(String::from("a.txt")));
+        assert_eq!(rest, ["prog", "b"]);
+    }
+
+    #[derive(Debug, Flags, ToArgs)]
+    struct Generic<T: fmt::Debug> {
+        #[flag(default = "5")]
+        id: T,
+        parent: Option<T>,
+        #[flag(required_if = ("id", "0"))]
+        reason: Option<String>,
+    }
+
+    #[test]
+    fn test_generic() {
+        let args = vec!["prog", "--parent=2"];
+        let args = args.into_iter().map(String::from);
+        let (flags, _) = Generic::<u64>::from_args(args).unwrap();
+        assert_eq!(flags.id, 5);
+        assert_eq!(flags.parent, Some(2));
+        assert_eq!(flags.to_args(), ["--parent=2"]);
+
+        let args = vec!["prog", "--id=0"].into_iter().map(String::from);
+        let err = Generic::<i8>::from_args(args).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::MissingRequired);
+        assert_eq!(Generic::<i8>::metadata()[0].type_name, "T");
+    }
+
+    #[test]
+    fn test_generic_bad_default() {
+        let args = vec![String::from("prog")];
+        assert_matches!(
+            Generic::<bool>::from_args(args).err().unwrap(),
+            FlagError::ParseError(err),
+            err.flag == "id" && err.input == "5" && err.type_str == "T"
+        );
+        let flags = Generic::<bool> {
+            id: false,
+            parent: None,
+            reason: None,
+        };
+        assert_eq!(flags.to_args(), ["--id=false"]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Id<T>(T);
+
+    impl<T: FromArg> FromArg for Id<T> {
+        fn from_arg(value: &str) -> FromArgResult<Self> {
+            T::from_arg(value).map(Id)
+        }
+    }
+
+    #[derive(Debug, Flags)]
+    struct NestedGeneric<T> {
+        #[flag(default = "7")]
+        id: Id<T>,
+        parent: Option<Id<T>>,
+    }
+
+    #[test]
+    fn test_nested_generic() {
+        let args = vec!["prog", "--parent=2"].into_iter().map(String::from);
+        let (flags, _) = NestedGeneric::<u8>::from_args(args).unwrap();
+        assert_eq!(flags.id, Id(7));
+        assert_eq!(flags.parent, Some(Id(2)));
+
+        let args = vec![String::from("prog")];
+        assert_matches!(
+            NestedGeneric::<bool>::from_args(args).err().unwrap(),
+            FlagError::ParseError(err),
+            err.flag == "id" && err.input == "7"
+        );
+    }
+
+    #[derive(Debug, Flags)]
+    struct GenericBorrowed<'a, 'b, T> {
+        name: &'a str,
+        tag: Option<&'b str>,
+        value: T,
+    }
+
+    #[test]
+    fn test_generic_borrowed() {
+        let args = ["prog", "--name=n", "--tag=t", "--value=1.5"];
+        let (flags, _) =
+            GenericBorrowed::<f32>::from_str_args(args.iter().cloned())
+                .unwrap();
+        assert_eq!(flags.name, "n");
+        assert_eq!(flags.tag, Some("t"));
+        assert_eq!(flags.value, 1.5);
+    }
 }
@@ -0,0 +1,192 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::help::{description_text, group_description, sections};
+use crate::{FlagInfo, GroupInfo};
+
+/// Returns a section 1 man page, in roff format, for the program `bin_name`
+/// with the given flags.
+///
+/// The first paragraph of `about` is the summary in the NAME section, and
+/// all of it makes up the DESCRIPTION section.  Flags are listed in the
+/// OPTIONS section, with each group in a subsection of its own.
+pub fn man_page(
+    bin_name: &str,
+    about: Option<&str>,
+    flags: &[FlagInfo],
+    groups: &[GroupInfo],
+) -> String {
+    let mut buf = format!(".TH {} 1\n", escape(&bin_name.to_uppercase()));
+
+    buf.push_str(".SH NAME\n");
+    match about.and_then(|about| paragraphs(about).into_iter().next()) {
+        Some(summary) => buf.push_str(&format!(
+            "{} \\- {}\n",
+            escape(bin_name),
+            escape(&summary)
+        )),
+        None => buf.push_str(&format!("{}\n", escape(bin_name))),
+    }
+
+    buf.push_str(".SH SYNOPSIS\n");
+    buf.push_str(&format!(".B {}\n", escape(bin_name)));
+    buf.push_str("[\\fIOPTIONS\\fR] [\\fIARGS\\fR]...\n");
+
+    if let Some(about) = about {
+        buf.push_str(".SH DESCRIPTION\n");
+        push_paragraphs(&mut buf, about);
+    }
+
+    if !flags.is_empty() {
+        buf.push_str(".SH OPTIONS\n");
+    }
+    for (group, section) in sections(flags) {
+        if let Some(name) = group {
+            buf.push_str(&format!(".SS {}\n", escape(name)));
+            if let Some(desc) = group_description(groups, name) {
+                push_paragraphs(&mut buf, desc);
+            }
+        }
+        for flag in section {
+            buf.push_str(".TP\n");
+            buf.push_str(&flag_name(flag));
+            buf.push('\n');
+            let text = description_text(flag);
+            if !text.is_empty() {
+                buf.push_str(&escape(text.trim_start()));
+                buf.push('\n');
+            }
+        }
+    }
+    buf
+}
+
+// Formats a flag like `\fB\-o\fR, \fB\-\-output\fR [\fIPATH\fR]`.
+fn flag_name(flag: &FlagInfo) -> String {
+    let mut buf = String::new();
+    if let Some(short) = flag.short {
+        buf.push_str(&format!("\\fB{}\\fR, ", escape(&format!("-{}", short))));
+    }
+    buf.push_str(&format!("\\fB{}\\fR", escape(&format!("--{}", flag.name))));
+    match flag.placeholder {
+        Some(placeholder) if flag.optional => {
+            buf.push_str(&format!(" [\\fI{}\\fR]", escape(placeholder)))
+        }
+        Some(placeholder) => {
+            buf.push_str(&format!(" \\fI{}\\fR", escape(placeholder)))
+        }
+        None => {}
+    }
+    buf
+}
+
+// Splits text at blank lines, joining the lines of each paragraph.
+pub(crate) fn paragraphs(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|para| para.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|para| !para.is_empty())
+        .collect()
+}
+
+fn push_paragraphs(buf: &mut String, text: &str) {
+    for (i, para) in paragraphs(text).iter().enumerate() {
+        if i > 0 {
+            buf.push_str(".PP\n");
+        }
+        buf.push_str(&escape(para));
+        buf.push('\n');
+    }
+}
+
+// Escapes text so that roff prints it as written.
+fn escape(s: &str) -> String {
+    let mut buf = String::new();
+    // Lines starting with these characters are requests to roff.
+    if s.starts_with('.') || s.starts_with('\'') {
+        buf.push_str("\\&");
+    }
+    for c in s.chars() {
+        match c {
+            '\\' => buf.push_str("\\e"),
+            '-' => buf.push_str("\\-"),
+            _ => buf.push(c),
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::string_flag;
+    use crate::FlagType;
+
+    fn flag(name: &'static str) -> FlagInfo<'static> {
+        string_flag(name).description("Does -things").optional(true)
+    }
+
+    #[test]
+    fn test_man_page() {
+        let flags = [
+            flag("output").short('o').placeholder("PATH"),
+            flag("port").placeholder("PORT").group("Networking"),
+            FlagInfo::new("retries", "String", FlagType::Int)
+                .placeholder("N")
+                .description("Does -things")
+                .default("3"),
+        ];
+        let groups = [GroupInfo {
+            name: "Networking",
+            description: Some(".Where to connect"),
+        }];
+        assert_eq!(
+            man_page(
+                "my-prog",
+                Some("Does a thing.\n\nIn great\ndetail."),
+                &flags,
+                &groups
+            ),
+            ".TH MY\\-PROG 1\n\
+             .SH NAME\n\
+             my\\-prog \\- Does a thing.\n\
+             .SH SYNOPSIS\n\
+             .B my\\-prog\n\
+             [\\fIOPTIONS\\fR] [\\fIARGS\\fR]...\n\
+             .SH DESCRIPTION\n\
+             Does a thing.\n\
+             .PP\n\
+             In great detail.\n\
+             .SH OPTIONS\n\
+             .TP\n\
+             \\fB\\-o\\fR, \\fB\\-\\-output\\fR [\\fIPATH\\fR]\n\
+             Does \\-things\n\
+             .TP\n\
+             \\fB\\-\\-retries\\fR \\fIN\\fR\n\
+             Does \\-things (defaults to 3)\n\
+             .SS Networking\n\
+             \\&.Where to connect\n\
+             .TP\n\
+             \\fB\\-\\-port\\fR [\\fIPORT\\fR]\n\
+             Does \\-things\n"
+        );
+    }
+
+    #[test]
+    fn test_man_page_without_about() {
+        let page = man_page("prog", None, &[], &[]);
+        assert!(page.contains(".SH NAME\nprog\n.SH SYNOPSIS\n"));
+        assert!(!page.contains(".SH DESCRIPTION"));
+        assert!(!page.contains(".SH OPTIONS"));
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The kind of value a flag holds, as determined from the type of its field.
+/// For `Option<T>` fields, this describes `T`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagType {
+    Bool,
+    Int,
+    Float,
+    String,
+    /// Any other type implementing [`ctflag::FromArg`].
+    ///
+    /// [`ctflag::FromArg`]: trait.FromArg.html
+    Other,
+}
+
+/// Describes a single flag.
+///
+/// Returned by [`ctflag::DescribeFlags::metadata()`], in the order the flags
+/// are declared.  New properties may be added, so a `FlagInfo` is built with
+/// [`FlagInfo::new()`] and the methods setting each property.
+///
+/// ```
+/// # use ctflag::{DescribeFlags, Flags};
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(short = 'o', desc = "Output file", placeholder = "PATH")]
+///     output: Option<String>,
+/// }
+///
+/// # fn main() {
+/// let flags = MyFlags::metadata();
+/// assert_eq!(flags[0].name, "output");
+/// assert_eq!(flags[0].short, Some('o'));
+/// assert_eq!(flags[0].type_name, "Option<String>");
+/// assert!(flags[0].optional);
+/// # }
+/// ```
+///
+/// [`ctflag::DescribeFlags::metadata()`]: trait.DescribeFlags.html#tymethod.metadata
+/// [`FlagInfo::new()`]: #method.new
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FlagInfo<'a> {
+    /// The long name of the flag, without the leading `--`.
+    pub name: &'a str,
+    /// The short name of the flag, without the leading `-`.
+    pub short: Option<char>,
+    /// The type of the field, as written in the struct.
+    pub type_name: &'a str,
+    /// The kind of value the flag holds.
+    pub flag_type: FlagType,
+    /// The text shown in place of the flag's value in help text, or `None`
+    /// if the flag does not take a value.
+    pub placeholder: Option<&'a str>,
+    /// The description of the flag.
+    pub description: Option<&'a str>,
+    /// The default value of the flag, if one was given.  String defaults are
+    /// unquoted.
+    pub default: Option<&'a str>,
+    /// The values the flag accepts, if there is a fixed set of them.
+    pub possible_values: &'a [&'a str],
+    /// The smallest value the flag accepts, set with `#[flag(min = ...)]`.
+    pub min: Option<&'a str>,
+    /// The largest value the flag accepts, set with `#[flag(max = ...)]`.
+    pub max: Option<&'a str>,
+    /// The flags that cannot be set together with this one, set with
+    /// `#[flag(conflicts_with = ...)]` or an exclusive group of flags.
+    pub conflicts_with: &'a [&'a str],
+    /// The flags that must be set whenever this one is, set with
+    /// `#[flag(requires = ...)]`.
+    pub requires: &'a [&'a str],
+    /// The flags that, when none of them is set, make this flag required.
+    /// Set with `#[flag(required_unless = ...)]`.
+    pub required_unless: &'a [&'a str],
+    /// The flags and values that make this flag required when another flag
+    /// has that value.  Set with `#[flag(required_if = ("...", "..."))]`.
+    pub required_if: &'a [(&'a str, &'a str)],
+    /// Whether the field is an `Option`, so the flag may be left unset.
+    pub optional: bool,
+    /// The help section the flag is listed under.
+    pub group: Option<&'a str>,
+    /// The function that completes the flag's value at runtime, set with
+    /// `#[flag(complete = ...)]`.
+    pub complete: Option<Completer>,
+}
+
+/// Returns the candidates for completing a flag's value, given the partial
+/// value typed so far.
+///
+/// See [`ctflag::handle_completion()`].
+///
+/// [`ctflag::handle_completion()`]: fn.handle_completion.html
+pub type Completer = fn(&str) -> Vec<String>;
+
+/// Describes a help section declared with `#[flags(group(...))]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupInfo<'a> {
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+}
+
+// Completers are compared by address, which is enough to tell flags apart.
+impl PartialEq for FlagInfo<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        let complete = |info: &Self| info.complete.map(|f| f as usize);
+        self.name == other.name
+            && self.short == other.short
+            && self.type_name == other.type_name
+            && self.flag_type == other.flag_type
+            && self.placeholder == other.placeholder
+            && self.description == other.description
+            && self.default == other.default
+            && self.possible_values == other.possible_values
+            && self.min == other.min
+            && self.max == other.max
+            && self.conflicts_with == other.conflicts_with
+            && self.requires == other.requires
+            && self.required_unless == other.required_unless
+            && self.required_if == other.required_if
+            && self.optional == other.optional
+            && self.group == other.group
+            && complete(self) == complete(other)
+    }
+}
+
+impl<'a> FlagInfo<'a> {
+    /// Returns a flag named `name`, whose field has the type `type_name`.
+    /// The flag has no other properties, and takes no value until given a
+    /// placeholder.
+    pub fn new(name: &'a str, type_name: &'a str, flag_type: FlagType) -> Self {
+        FlagInfo {
+            name,
+            short: None,
+            type_name,
+            flag_type,
+            placeholder: None,
+            description: None,
+            default: None,
+            possible_values: &[],
+            min: None,
+            max: None,
+            conflicts_with: &[],
+            requires: &[],
+            required_unless: &[],
+            required_if: &[],
+            optional: false,
+            group: None,
+            complete: None,
+        }
+    }
+
+    /// Sets the short name of the flag.
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    /// Sets the text shown in place of the flag's value.
+    pub fn placeholder(mut self, placeholder: &'a str) -> Self {
+        self.placeholder = Some(placeholder);
+        self
+    }
+
+    /// Sets the description of the flag.
+    pub fn description(mut self, description: &'a str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Sets the default value of the flag, unquoted.
+    pub fn default(mut self, default: &'a str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    /// Sets the values the flag accepts.
+    pub fn possible_values(mut self, values: &'a [&'a str]) -> Self {
+        self.possible_values = values;
+        self
+    }
+
+    /// Sets the smallest value the flag accepts.
+    pub fn min(mut self, min: &'a str) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    /// Sets the largest value the flag accepts.
+    pub fn max(mut self, max: &'a str) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Sets the flags that cannot be set together with this one.
+    pub fn conflicts_with(mut self, flags: &'a [&'a str]) -> Self {
+        self.conflicts_with = flags;
+        self
+    }
+
+    /// Sets the flags that must be set whenever this one is.
+    pub fn requires(mut self, flags: &'a [&'a str]) -> Self {
+        self.requires = flags;
+        self
+    }
+
+    /// Sets the flags that make this flag required when none of them is set.
+    pub fn required_unless(mut self, flags: &'a [&'a str]) -> Self {
+        self.required_unless = flags;
+        self
+    }
+
+    /// Sets the flags and values that make this flag required.
+    pub fn required_if(mut self, conditions: &'a [(&'a str, &'a str)]) -> Self {
+        self.required_if = conditions;
+        self
+    }
+
+    /// Sets whether the flag may be left unset.
+    pub fn optional(mut self, optional: bool) -> Self {
+        self.optional = optional;
+        self
+    }
+
+    /// Sets the help section the flag is listed under.
+    pub fn group(mut self, group: &'a str) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    /// Sets the function that completes the flag's value at runtime.
+    pub fn complete(mut self, complete: Completer) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
+    /// Returns true if the flag must be followed by a value.  Only `bool`
+    /// flags can be set without one.
+    pub fn takes_value(&self) -> bool {
+        self.placeholder.is_some()
+    }
+
+    /// Returns the default value formatted as it appears in help text, with
+    /// string values quoted.
+    pub fn default_text(&self) -> Option<String> {
+        self.default.map(|def| match self.flag_type {
+            FlagType::String | FlagType::Other => format!("{:?}", def),
+            _ => String::from(def),
+        })
+    }
+}
+
+// A flag taking a `String` value, which the tests of each generator build on.
+#[cfg(test)]
+pub(crate) fn string_flag(name: &'static str) -> FlagInfo<'static> {
+    FlagInfo::new(name, "String", FlagType::String).placeholder("VALUE")
+}
@@ -0,0 +1,242 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::help::{group_description, sections};
+use crate::man::paragraphs;
+use crate::{FlagInfo, GroupInfo};
+
+/// Returns a reference page for the program `bin_name`, in Markdown.
+///
+/// The page starts with `about`, followed by a table of the flags listing
+/// their names, types, defaults and descriptions.  Each group of flags has
+/// a table of its own.
+pub fn markdown_reference(
+    bin_name: &str,
+    about: Option<&str>,
+    flags: &[FlagInfo],
+    groups: &[GroupInfo],
+) -> String {
+    let mut buf = format!("# {}\n", bin_name);
+    if let Some(about) = about {
+        for para in paragraphs(about) {
+            buf.push_str(&format!("\n{}\n", para));
+        }
+    }
+    if !flags.is_empty() {
+        buf.push_str("\n## Options\n");
+    }
+    for (group, section) in sections(flags) {
+        if let Some(name) = group {
+            buf.push_str(&format!("\n### {}\n", name));
+            if let Some(desc) = group_description(groups, name) {
+                for para in paragraphs(desc) {
+                    buf.push_str(&format!("\n{}\n", para));
+                }
+            }
+        }
+        buf.push_str("\n| Flag | Type | Default | Description |\n");
+        buf.push_str("| --- | --- | --- | --- |\n");
+        for flag in section {
+            let names = flag_names(flag)
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let default = flag
+                .default_text()
+                .map(|def| format!("`{}`", def))
+                .unwrap_or_default();
+            let cells = [
+                names,
+                format!("`{}`", flag.type_name),
+                default,
+                String::from(flag.description.unwrap_or("")),
+            ];
+            for cell in cells.iter() {
+                buf.push_str(&format!("| {} ", markdown_escape(cell)));
+            }
+            buf.push_str("|\n");
+        }
+    }
+    buf
+}
+
+/// Returns a reference page for the program `bin_name`, as an HTML
+/// fragment.
+///
+/// The page has the same content as the one returned by
+/// [`ctflag::markdown_reference()`], without any styling, so that it can be
+/// embedded in another page.
+///
+/// [`ctflag::markdown_reference()`]: fn.markdown_reference.html
+pub fn html_reference(
+    bin_name: &str,
+    about: Option<&str>,
+    flags: &[FlagInfo],
+    groups: &[GroupInfo],
+) -> String {
+    let mut buf = format!("<h1>{}</h1>\n", html_escape(bin_name));
+    if let Some(about) = about {
+        push_html_paragraphs(&mut buf, about);
+    }
+    if !flags.is_empty() {
+        buf.push_str("<h2>Options</h2>\n");
+    }
+    for (group, section) in sections(flags) {
+        if let Some(name) = group {
+            buf.push_str(&format!("<h3>{}</h3>\n", html_escape(name)));
+            if let Some(desc) = group_description(groups, name) {
+                push_html_paragraphs(&mut buf, desc);
+            }
+        }
+        buf.push_str("<table>\n<thead>\n");
+        buf.push_str(
+            "<tr><th>Flag</th><th>Type</th><th>Default</th>\
+             <th>Description</th></tr>\n",
+        );
+        buf.push_str("</thead>\n<tbody>\n");
+        for flag in section {
+            let names = flag_names(flag)
+                .iter()
+                .map(|name| format!("<code>{}</code>", html_escape(name)))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let default = flag
+                .default_text()
+                .map(|def| format!("<code>{}</code>", html_escape(&def)))
+                .unwrap_or_default();
+            buf.push_str(&format!(
+                "<tr><td>{}</td><td><code>{}</code></td><td>{}</td>\
+                 <td>{}</td></tr>\n",
+                names,
+                html_escape(flag.type_name),
+                default,
+                html_escape(flag.description.unwrap_or(""))
+            ));
+        }
+        buf.push_str("</tbody>\n</table>\n");
+    }
+    buf
+}
+
+// The short and long names of a flag, with the long name followed by the
+// flag's placeholder like in help text.
+fn flag_names(flag: &FlagInfo) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(short) = flag.short {
+        names.push(format!("-{}", short));
+    }
+    names.push(match flag.placeholder {
+        Some(placeholder) if flag.optional => {
+            format!("--{} [{}]", flag.name, placeholder)
+        }
+        Some(placeholder) => format!("--{} {}", flag.name, placeholder),
+        None => format!("--{}", flag.name),
+    });
+    names
+}
+
+// Keeps text within a single table cell.
+fn markdown_escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+fn push_html_paragraphs(buf: &mut String, text: &str) {
+    for para in paragraphs(text) {
+        buf.push_str(&format!("<p>{}</p>\n", html_escape(&para)));
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    let mut buf = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            _ => buf.push(c),
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::string_flag;
+    use crate::FlagType;
+
+    fn flags() -> Vec<FlagInfo<'static>> {
+        vec![
+            FlagInfo::new("output", "Option<String>", FlagType::String)
+                .short('o')
+                .placeholder("PATH")
+                .description("Output file, a|b")
+                .optional(true),
+            string_flag("host")
+                .placeholder("HOST")
+                .default("localhost")
+                .group("Networking"),
+        ]
+    }
+
+    const GROUPS: [GroupInfo<'static>; 1] = [GroupInfo {
+        name: "Networking",
+        description: Some("Where to connect"),
+    }];
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            markdown_reference("prog", Some("Does\na thing."), &flags(), &GROUPS),
+            "# prog\n\
+             \n\
+             Does a thing.\n\
+             \n\
+             ## Options\n\
+             \n\
+             | Flag | Type | Default | Description |\n\
+             | --- | --- | --- | --- |\n\
+             | `-o`, `--output [PATH]` | `Option<String>` |  | Output file, a\\|b |\n\
+             \n\
+             ### Networking\n\
+             \n\
+             Where to connect\n\
+             \n\
+             | Flag | Type | Default | Description |\n\
+             | --- | --- | --- | --- |\n\
+             | `--host HOST` | `String` | `\"localhost\"` |  |\n"
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            html_reference("prog", None, &flags()[..1], &[]),
+            "<h1>prog</h1>\n\
+             <h2>Options</h2>\n\
+             <table>\n\
+             <thead>\n\
+             <tr><th>Flag</th><th>Type</th><th>Default</th><th>Description</th></tr>\n\
+             </thead>\n\
+             <tbody>\n\
+             <tr><td><code>-o</code>, <code>--output [PATH]</code></td>\
+             <td><code>Option&lt;String&gt;</code></td><td></td>\
+             <td>Output file, a|b</td></tr>\n\
+             </tbody>\n\
+             </table>\n"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::style::{ColorChoice, Stream, Style};
+use crate::{FlagError, FlagErrors, StyledFlagError};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Renders errors together with the command-line that caused them, pointing
+/// at the offending argument.
+///
+/// The arguments are reprinted quoted for the shell, and the flag the error
+/// is about is underlined with `^`.  When the value of the flag was the next
+/// argument, as in `--count x`, it is underlined too.  Errors that aren't
+/// about an argument, like a missing required flag, are rendered without the
+/// command-line.
+///
+/// ```
+/// # use ctflag::{ErrorRenderer, Flags};
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     count: i32,
+/// }
+/// # fn main() {
+/// let args = vec!["prog", "--count", "x"];
+/// let args: Vec<String> = args.into_iter().map(String::from).collect();
+/// let err = MyFlags::from_args(args.clone()).err().unwrap();
+/// assert_eq!(
+///     ErrorRenderer::new().render(&err, &args),
+///     "failed to parse \"x\" as i32 type for argument \"count\": \
+///      invalid digit found in string\n  \
+///      prog --count x\n       \
+///           ^^^^^^^^^"
+/// );
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ErrorRenderer {
+    color: ColorChoice,
+}
+
+impl ErrorRenderer {
+    /// Creates a renderer that doesn't use color.
+    pub fn new() -> Self {
+        ErrorRenderer {
+            color: ColorChoice::Never,
+        }
+    }
+
+    /// Sets whether the errors are styled.  [`ctflag::ColorChoice::Auto`]
+    /// styles the errors if stderr is a terminal.
+    ///
+    /// [`ctflag::ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Renders `err`, which was returned when parsing `args`.  `args` must
+    /// include the program name, as passed to [`ctflag::Flags::from_args()`].
+    ///
+    /// The underline is as wide as the number of graphemes in each argument,
+    /// not its width in columns, so it's misaligned after wide characters
+    /// like CJK ideographs or emoji.
+    ///
+    /// [`ctflag::Flags::from_args()`]: trait.Flags.html#tymethod.from_args
+    pub fn render(&self, err: &FlagError, args: &[String]) -> String {
+        let style = Style::new(self.color.should_color(Stream::Stderr));
+        let mut buf = format!("{}", StyledFlagError { err, style });
+        let index = match err.index() {
+            Some(index) if index < args.len() => index,
+            _ => return buf,
+        };
+        let (first, last) = (index, index + value_args(err, &args[index..]));
+
+        let mut line = String::new();
+        let mut underline = String::new();
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                line.push(' ');
+                underline.push(if i > first && i <= last { '^' } else { ' ' });
+            }
+            let arg = shell_quote(arg);
+            let width = arg.graphemes(true).count();
+            let mark = if i >= first && i <= last { '^' } else { ' ' };
+            line.push_str(&arg);
+            // `std::iter::repeat_n` needs Rust 1.82.
+            #[allow(clippy::manual_repeat_n)]
+            underline.extend(std::iter::repeat(mark).take(width));
+        }
+        buf.push_str("\n  ");
+        buf.push_str(&line);
+        buf.push_str("\n  ");
+        buf.push_str(&style.invalid(underline.trim_end()));
+        buf
+    }
+
+    /// Renders each of `errors`, separated by blank lines.
+    pub fn render_all(&self, errors: &FlagErrors, args: &[String]) -> String {
+        errors
+            .iter()
+            .map(|err| self.render(err, args))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+}
+
+// The number of arguments after the flag that hold its value, which is 1 if
+// the value that failed was given as the next argument.
+fn value_args(err: &FlagError, args: &[String]) -> usize {
+    let input = match err {
+        FlagError::ParseError(err) => &err.input,
+        FlagError::InvalidChoice(err) => &err.input,
+        _ => return 0,
+    };
+    match args {
+        [flag, value, ..] if !flag.contains('=') && value == input => 1,
+        _ => 0,
+    }
+}
+
+// Quotes `arg` so that it could be pasted back into a POSIX shell.
+fn shell_quote(arg: &str) -> String {
+    let is_plain = |c: char| c.is_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        return String::from(arg);
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{at_index, unrecognized_arg};
+    use crate::{ConflictStruct, MissingRequiredStruct, RequiredCondition};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().cloned().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("--name=a.txt"), "--name=a.txt");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_render_unrecognized() {
+        let err = at_index(unrecognized_arg(String::from("--nope"), None), 2);
+        assert_eq!(
+            ErrorRenderer::new()
+                .render(&err, &args(&["prog", "a b", "--nope", "c"])),
+            "unrecognized argument \"--nope\"\n  \
+             prog 'a b' --nope c\n  \
+             \x20          ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_render_conflict() {
+        let err = FlagError::Conflict(ConflictStruct {
+            flag: String::from("json"),
+            other: String::from("yaml"),
+            index: Some(2),
+        });
+        assert_eq!(
+            ErrorRenderer::new()
+                .render(&err, &args(&["prog", "--yaml", "--json"])),
+            "argument \"json\" cannot be used with \"yaml\"\n  \
+             prog --yaml --json\n  \
+             \x20           ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_render_without_index() {
+        let err = FlagError::MissingRequired(MissingRequiredStruct {
+            flag: String::from("region"),
+            condition: RequiredCondition::Unless(vec![String::from("local")]),
+        });
+        assert_eq!(
+            ErrorRenderer::new().render(&err, &args(&["prog"])),
+            "missing argument \"region\", which is required unless \"local\" \
+             is set"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::completion::find_flag;
+use crate::internal::{Arg, FlagIterator, FlagStruct};
+use crate::style::ColorChoice;
+use crate::{internal, ErrorRenderer, FlagInfo, Flags, HelpRenderer};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+
+// The exit code used when the arguments can't be parsed, as used by most
+// command-line tools.
+const DEFAULT_ERROR_CODE: i32 = 2;
+
+/// Parses the command-line arguments of the process and calls `main` with
+/// the flags and the remaining arguments, using a [`ctflag::Runner`] with its
+/// default settings.
+///
+/// ```no_run
+/// # use ctflag::Flags;
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     #[flag(desc = "The floopy floops the whoop")]
+///     enable_floopy: bool,
+/// }
+///
+/// fn main() {
+///     ctflag::run(|flags: MyFlags, args| {
+///         // ...
+///     });
+/// }
+/// ```
+///
+/// [`ctflag::Runner`]: struct.Runner.html
+pub fn run<F, M, R>(main: M) -> R
+where
+    F: Flags,
+    M: FnOnce(F, Vec<String>) -> R,
+{
+    Runner::new().run(main)
+}
+
+/// Parses the command-line arguments and calls the program's main function,
+/// handling help, version and errors the same way for every program.
+///
+/// - When run by a script from [`ctflag::completion_script()`], the
+///   candidates for completing the command-line are printed as by
+///   [`ctflag::DescribeFlags::handle_completion()`], and the process exits.
+/// - `--help` and `-h` print the description of the program and its flags to
+///   stdout and exit with code 0.  They are looked for up to `--`, and not in
+///   the values of flags.
+/// - `--version` and `-V` print the version set with
+///   [`ctflag::Runner::version()`] to stdout and exit with code 0.
+/// - Errors are printed to stderr, pointing at the argument that caused them,
+///   followed by a hint to use `--help`.  The process exits with code 2, or
+///   the code set with [`ctflag::Runner::error_code()`].  Every error is
+///   printed, as found by [`ctflag::Flags::from_args_all()`].
+///
+/// Flags declared by the struct take precedence, so a struct with its own
+/// `help` field receives it like any other flag.
+///
+/// The arguments are parsed like [`ctflag::Flags::from_args_os()`], so that
+/// `PathBuf` and `OsString` flags receive values that aren't valid UTF-8 as
+/// is.  The remaining arguments passed to `main` are converted lossily.
+///
+/// ```no_run
+/// # use ctflag::{Flags, Runner};
+/// ##[derive(Flags)]
+/// struct MyFlags {
+///     enable_floopy: bool,
+/// }
+///
+/// fn main() -> std::io::Result<()> {
+///     Runner::new()
+///         .version(env!("CARGO_PKG_VERSION"))
+///         // EX_USAGE from sysexits.h.
+///         .error_code(64)
+///         .run(|flags: MyFlags, args| {
+///             // ...
+///             Ok(())
+///         })
+/// }
+/// ```
+///
+/// [`ctflag::completion_script()`]: fn.completion_script.html
+/// [`ctflag::DescribeFlags::handle_completion()`]: trait.DescribeFlags.html#method.handle_completion
+/// [`ctflag::Flags::from_args_all()`]: trait.Flags.html#method.from_args_all
+/// [`ctflag::Flags::from_args_os()`]: trait.Flags.html#method.from_args_os
+/// [`ctflag::Runner::version()`]: struct.Runner.html#method.version
+/// [`ctflag::Runner::error_code()`]: struct.Runner.html#method.error_code
+#[derive(Clone, Debug)]
+pub struct Runner {
+    version: Option<String>,
+    error_code: i32,
+    color: ColorChoice,
+}
+
+// What the runner does with the arguments.
+#[derive(Debug)]
+enum Action<F> {
+    Run(F, Vec<String>),
+    Print(String),
+    Fail(String),
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner::new()
+    }
+}
+
+impl Runner {
+    /// Creates a runner without a version, which exits with code 2 on
+    /// errors and styles its output if it is written to a terminal.
+    pub fn new() -> Self {
+        Runner {
+            version: None,
+            error_code: DEFAULT_ERROR_CODE,
+            color: ColorChoice::Auto,
+        }
+    }
+
+    /// Sets the version printed by `--version`, usually
+    /// `env!("CARGO_PKG_VERSION")`.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(String::from(version));
+        self
+    }
+
+    /// Sets the exit code used when the arguments can't be parsed.
+    pub fn error_code(mut self, code: i32) -> Self {
+        self.error_code = code;
+        self
+    }
+
+    /// Sets whether help and errors are styled.
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Parses the command-line arguments of the process and calls `main`
+    /// with the flags and the remaining arguments, returning its result.
+    /// Exits the process instead if help or the version was requested, or if
+    /// the arguments can't be parsed.
+    pub fn run<F, M, R>(&self, main: M) -> R
+    where
+        F: Flags,
+        M: FnOnce(F, Vec<String>) -> R,
+    {
+        F::handle_completion();
+        self.run_os(env::args_os().collect(), main)
+    }
+
+    /// Like [`ctflag::Runner::run()`], with the given arguments instead of
+    /// those of the process.
+    ///
+    /// [`ctflag::Runner::run()`]: struct.Runner.html#method.run
+    pub fn run_with_args<F, M, R>(&self, args: Vec<String>, main: M) -> R
+    where
+        F: Flags,
+        M: FnOnce(F, Vec<String>) -> R,
+    {
+        self.run_os(args.into_iter().map(OsString::from).collect(), main)
+    }
+
+    fn run_os<F, M, R>(&self, args: Vec<OsString>, main: M) -> R
+    where
+        F: Flags,
+        M: FnOnce(F, Vec<String>) -> R,
+    {
+        match self.action(args) {
+            Action::Run(flags, args) => main(flags, args),
+            Action::Print(text) => {
+                println!("{}", text);
+                process::exit(0);
+            }
+            Action::Fail(text) => {
+                eprintln!("{}", text);
+                process::exit(self.error_code);
+            }
+        }
+    }
+
+    fn action<F: Flags>(&self, os_args: Vec<OsString>) -> Action<F> {
+        let bin_name = os_args
+            .first()
+            .and_then(|arg| Path::new(arg).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("program"));
+        let args = os_args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<String>>();
+        let flags = F::metadata();
+        let mut iter =
+            FlagIterator::from_args(args.iter().skip(1).map(String::as_str));
+        while let Some(arg) = iter.next() {
+            let (key, val) = match arg {
+                Arg::Flag(FlagStruct { key, val }) => (key, val),
+                Arg::Arg(_) => continue,
+            };
+            if key == "--" {
+                break;
+            }
+            if is_builtin(&flags, key, "help", 'h') {
+                return Action::Print(self.help::<F>(&bin_name));
+            }
+            if let Some(version) = &self.version {
+                if is_builtin(&flags, key, "version", 'V') {
+                    return Action::Print(format!("{} {}", bin_name, version));
+                }
+            }
+            // Skip the value of `--key value`, as the derive does.
+            if val.is_none()
+                && find_flag(&flags, key).is_some_and(FlagInfo::takes_value)
+            {
+                iter.next_arg();
+            }
+        }
+        match F::parse_args_os(os_args, false).map(internal::string_args) {
+            Ok((flags, rest)) => Action::Run(flags, rest),
+            Err(errors) => Action::Fail(format!(
+                "{}\n\nFor more information, try '{} --help'.",
+                ErrorRenderer::new()
+                    .color(self.color)
+                    .render_all(&errors, &args),
+                bin_name
+            )),
+        }
+    }
+
+    fn help<F: Flags>(&self, bin_name: &str) -> String {
+        let mut buf = String::new();
+        if let Some(about) = F::about() {
+            buf.push_str(about);
+            buf.push_str("\n\n");
+        }
+        buf.push_str(&format!("Usage: {} [OPTIONS]\n\n", bin_name));
+        let help = HelpRenderer::for_terminal().color(self.color).render::<F>();
+        buf.push_str(help.trim_end());
+        buf
+    }
+}
+
+// Returns true if `arg` is the flag `--name` or `-short`, and the flags don't
+// define it themselves.
+fn is_builtin(flags: &[FlagInfo], arg: &str, name: &str, short: char) -> bool {
+    match arg.strip_prefix("--") {
+        Some(long) => long == name && !flags.iter().any(|f| f.name == name),
+        None => {
+            arg == format!("-{}", short)
+                && !flags.iter().any(|f| f.short == Some(short))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // Since we are inside the ctflag crate, alias crate to ctflag.
+    use crate as ctflag;
+
+    /// Floops whoops.
+    #[derive(Debug, Flags)]
+    struct Floopy {
+        #[flag(desc = "How many")]
+        count: i32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Flags)]
+    struct OwnHelp {
+        help: bool,
+        #[flag(short = 'V')]
+        verbose: bool,
+    }
+
+    fn action<F: Flags>(runner: &Runner, args: &[&str]) -> Action<F> {
+        runner.action(args.iter().cloned().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn test_run() {
+        let runner = Runner::new().color(ColorChoice::Never);
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--count=3", "a"]),
+            Action::Run(flags, args),
+            flags.count == 3 && args == ["prog", "a"]
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        let runner = Runner::new().color(ColorChoice::Never);
+        assert_matches!(
+            action::<Floopy>(&runner, &["/bin/prog", "--count=x", "-h"]),
+            Action::Print(text),
+            text.starts_with("Floops whoops.\n\nUsage: prog [OPTIONS]\n\n")
+                && text.contains("--count VALUE    How many")
+        );
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--count", "3", "-h"]),
+            Action::Print(_)
+        );
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--count", "-h"]),
+            Action::Fail(text),
+            text.starts_with("missing value")
+        );
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--", "-h"]),
+            Action::Fail(_)
+        );
+        assert_matches!(
+            action::<OwnHelp>(&runner, &["prog", "--help"]),
+            Action::Run(flags, _),
+            flags.help
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        let runner = Runner::new().color(ColorChoice::Never).version("1.2");
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--version"]),
+            Action::Print(text),
+            text == "prog 1.2"
+        );
+        assert_matches!(
+            action::<OwnHelp>(&runner, &["prog", "-V"]),
+            Action::Run(flags, _),
+            flags.verbose
+        );
+        assert_matches!(
+            action::<Floopy>(&Runner::new(), &["prog", "--version"]),
+            Action::Fail(_)
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        let runner = Runner::new().color(ColorChoice::Never);
+        assert_matches!(
+            action::<Floopy>(&runner, &["prog", "--cont=3"]),
+            Action::Fail(text),
+            text == "unrecognized argument \"--cont\"; did you mean \
+                     \"--count\"?\n  \
+                     prog --cont=3\n       \
+                     ^^^^^^^^\n\n\
+                     For more information, try 'prog --help'."
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{FlagInfo, FlagType};
+
+const SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Returns a JSON Schema describing the flags of the program `bin_name`, as
+/// an object with a property for each flag.
+///
+/// The type of each property follows the flag's [`ctflag::FlagType`].  Flags
+/// with a fixed set of possible values are described with an `enum`.
+/// Defaults and descriptions are included when the flag has them.
+///
+/// [`ctflag::FlagType`]: enum.FlagType.html
+pub fn json_schema(
+    bin_name: &str,
+    about: Option<&str>,
+    flags: &[FlagInfo],
+) -> String {
+    let mut schema = vec![
+        (String::from("$schema"), Json::string(SCHEMA_DRAFT)),
+        (String::from("title"), Json::string(bin_name)),
+    ];
+    if let Some(about) = about {
+        schema.push((String::from("description"), Json::string(about)));
+    }
+    schema.push((String::from("type"), Json::string("object")));
+    let properties = flags
+        .iter()
+        .map(|flag| (String::from(flag.name), flag_schema(flag)))
+        .collect();
+    schema.push((String::from("properties"), Json::Object(properties)));
+    schema.push((String::from("additionalProperties"), Json::Bool(false)));
+
+    let mut buf = String::new();
+    Json::Object(schema).write(&mut buf, 0);
+    buf.push('\n');
+    buf
+}
+
+fn flag_schema(flag: &FlagInfo) -> Json {
+    let mut schema = vec![(
+        String::from("type"),
+        Json::string(match flag.flag_type {
+            FlagType::Bool => "boolean",
+            FlagType::Int => "integer",
+            FlagType::Float => "number",
+            FlagType::String | FlagType::Other => "string",
+        }),
+    )];
+    if let Some(desc) = flag.description {
+        schema.push((String::from("description"), Json::string(desc)));
+    }
+    if !flag.possible_values.is_empty() {
+        let values = flag.possible_values.iter().map(|v| Json::string(v));
+        schema.push((String::from("enum"), Json::Array(values.collect())));
+    }
+    if let Some(def) = flag.default {
+        schema.push((String::from("default"), default_value(flag, def)));
+    }
+    if let Some(min) = flag.min {
+        schema.push((String::from("minimum"), Json::Number(String::from(min))));
+    }
+    if let Some(max) = flag.max {
+        schema.push((String::from("maximum"), Json::Number(String::from(max))));
+    }
+    Json::Object(schema)
+}
+
+// Defaults are written as Rust literals, which aren't always valid JSON.
+// Numbers that can't be parsed are kept as strings.
+fn default_value(flag: &FlagInfo, def: &str) -> Json {
+    let number = strip_suffix(def).replace('_', "");
+    match flag.flag_type {
+        FlagType::Bool if def == "true" || def == "false" => {
+            Json::Bool(def == "true")
+        }
+        FlagType::Int if number.parse::<i128>().is_ok() => Json::Number(number),
+        FlagType::Float => match number.parse::<f64>() {
+            Ok(val) if val.is_finite() => Json::Number(format!("{}", val)),
+            _ => Json::string(def),
+        },
+        _ => Json::string(def),
+    }
+}
+
+// Strips the type suffix of a number literal, like `1i64` or `2.5f32`.
+fn strip_suffix(def: &str) -> &str {
+    const SUFFIXES: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+        "u128", "usize", "f32", "f64",
+    ];
+    SUFFIXES
+        .iter()
+        .find_map(|suffix| def.strip_suffix(suffix))
+        .unwrap_or(def)
+}
+
+enum Json {
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn string(s: &str) -> Json {
+        Json::String(String::from(s))
+    }
+
+    // Writes the value pretty-printed, with nested values indented by two
+    // spaces per level.
+    fn write(&self, buf: &mut String, indent: usize) {
+        match self {
+            Json::Bool(val) => {
+                buf.push_str(if *val { "true" } else { "false" })
+            }
+            Json::Number(val) => buf.push_str(val),
+            Json::String(val) => write_string(buf, val),
+            Json::Array(values) => {
+                buf.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        buf.push_str(", ");
+                    }
+                    value.write(buf, indent);
+                }
+                buf.push(']');
+            }
+            Json::Object(members) if members.is_empty() => buf.push_str("{}"),
+            Json::Object(members) => {
+                buf.push_str("{\n");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    push_indent(buf, indent + 1);
+                    write_string(buf, key);
+                    buf.push_str(": ");
+                    value.write(buf, indent + 1);
+                    if i + 1 < members.len() {
+                        buf.push(',');
+                    }
+                    buf.push('\n');
+                }
+                push_indent(buf, indent);
+                buf.push('}');
+            }
+        }
+    }
+}
+
+fn push_indent(buf: &mut String, indent: usize) {
+    for _ in 0..indent {
+        buf.push_str("  ");
+    }
+}
+
+fn write_string(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                buf.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::string_flag;
+
+    fn flag(name: &'static str, flag_type: FlagType) -> FlagInfo<'static> {
+        FlagInfo {
+            flag_type,
+            ..string_flag(name)
+        }
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let flags = [
+            flag("verbose", FlagType::Bool)
+                .description("Be \"loud\"")
+                .default("true"),
+            flag("count", FlagType::Int).default("1_000"),
+            flag("ratio", FlagType::Float).default("2.5"),
+            flag("mode", FlagType::Other)
+                .possible_values(&["fast", "slow"])
+                .default("fast"),
+        ];
+        assert_eq!(
+            json_schema("prog", Some("Does things"), &flags),
+            r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "prog",
+  "description": "Does things",
+  "type": "object",
+  "properties": {
+    "verbose": {
+      "type": "boolean",
+      "description": "Be \"loud\"",
+      "default": true
+    },
+    "count": {
+      "type": "integer",
+      "default": 1000
+    },
+    "ratio": {
+      "type": "number",
+      "default": 2.5
+    },
+    "mode": {
+      "type": "string",
+      "enum": ["fast", "slow"],
+      "default": "fast"
+    }
+  },
+  "additionalProperties": false
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_suffixed_defaults() {
+        let count = flag("count", FlagType::Int).default("1_000i64");
+        assert!(matches!(
+            default_value(&count, "1_000i64"),
+            Json::Number(val),
+            val == "1000"
+        ));
+        let ratio = flag("ratio", FlagType::Float).default("2.5f32");
+        assert!(matches!(
+            default_value(&ratio, "2.5f32"),
+            Json::Number(val),
+            val == "2.5"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_json_defaults_are_strings() {
+        let flag = flag("ratio", FlagType::Float).default("1e400");
+        assert!(matches!(
+            default_value(&flag, "1e400"),
+            Json::String(val),
+            val == "1e400"
+        ));
+    }
+}
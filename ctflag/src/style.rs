@@ -0,0 +1,144 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Controls whether help text and errors are styled with ANSI escape codes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Style the output if the stream it is written to is a terminal.
+    ///
+    /// A non-empty `NO_COLOR` environment variable disables styling, and a
+    /// `CLICOLOR_FORCE` environment variable other than `0` enables it
+    /// even if the stream is not a terminal.
+    Auto,
+    /// Always style the output.
+    Always,
+    /// Never style the output.
+    #[default]
+    Never,
+}
+
+/// The stream that styled output is written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl ColorChoice {
+    /// Returns true if output written to `stream` should be styled.
+    pub fn should_color(self, stream: Stream) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if env_is_set("NO_COLOR", "") {
+                    false
+                } else if env_is_set("CLICOLOR_FORCE", "0") {
+                    true
+                } else if env::var_os("TERM").is_some_and(|t| t == "dumb") {
+                    false
+                } else {
+                    match stream {
+                        Stream::Stdout => io::stdout().is_terminal(),
+                        Stream::Stderr => io::stderr().is_terminal(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Returns true if the environment variable `key` is set to something other
+// than `off_value`.
+fn env_is_set(key: &str, off_value: &str) -> bool {
+    match env::var_os(key) {
+        Some(val) => val != off_value,
+        None => false,
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// Applies ANSI styles to the different parts of help text and errors.
+/// A disabled `Style` returns its input unchanged.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Style { enabled }
+    }
+
+    pub(crate) fn plain() -> Self {
+        Style::new(false)
+    }
+
+    /// Section headers, like `OPTIONS:`.
+    pub(crate) fn header(self, s: &str) -> String {
+        self.paint("\x1b[1;4m", s)
+    }
+
+    /// Flag names, like `--output`.
+    pub(crate) fn literal(self, s: &str) -> String {
+        self.paint("\x1b[1m", s)
+    }
+
+    /// Placeholders for a flag's value, like `PATH`.
+    pub(crate) fn placeholder(self, s: &str) -> String {
+        self.paint("\x1b[36m", s)
+    }
+
+    /// User input that caused an error.
+    pub(crate) fn invalid(self, s: &str) -> String {
+        self.paint("\x1b[1;33m", s)
+    }
+
+    fn paint(self, code: &str, s: &str) -> String {
+        if self.enabled {
+            format!("{}{}{}", code, s, RESET)
+        } else {
+            String::from(s)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_style_is_unchanged() {
+        let style = Style::plain();
+        assert_eq!(style.header("OPTIONS:"), "OPTIONS:");
+        assert_eq!(style.literal("--flag"), "--flag");
+        assert_eq!(style.invalid("oops"), "oops");
+    }
+
+    #[test]
+    fn test_style() {
+        let style = Style::new(true);
+        assert_eq!(style.literal("--flag"), "\x1b[1m--flag\x1b[0m");
+    }
+
+    #[test]
+    fn test_forced_choices() {
+        assert!(ColorChoice::Always.should_color(Stream::Stdout));
+        assert!(!ColorChoice::Never.should_color(Stream::Stderr));
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use syn::parse::{Parse, ParseStream};
+
+// A single argument of a `#[flag(...)]` or `#[flags(...)]` attribute.
+// Unlike `syn::Meta`, values can be any expression, like a path to a function.
+pub(super) struct AttrArg {
+    pub(super) name: syn::Ident,
+    pub(super) value: AttrValue,
+}
+
+pub(super) enum AttrValue {
+    // `name`
+    Word,
+    // `name = expr`
+    Expr(syn::Expr),
+    // `name(args...)`
+    List(Vec<AttrArg>),
+}
+
+impl AttrArg {
+    // Returns the value of a `name = expr` argument.
+    pub(super) fn expr(&self) -> syn::Result<&syn::Expr> {
+        match &self.value {
+            AttrValue::Expr(expr) => Ok(expr),
+            _ => Err(syn::Error::new_spanned(
+                &self.name,
+                format!("Expected '{} = ...'", self.name),
+            )),
+        }
+    }
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse::<syn::Ident>()?;
+        let value = if input.peek(syn::Token![=]) {
+            input.parse::<syn::Token![=]>()?;
+            AttrValue::Expr(input.parse()?)
+        } else if input.peek(syn::token::Paren) {
+            AttrValue::List(parse_parenthesized(input)?)
+        } else {
+            AttrValue::Word
+        };
+        Ok(AttrArg { name, value })
+    }
+}
+
+fn parse_parenthesized(input: ParseStream) -> syn::Result<Vec<AttrArg>> {
+    let content;
+    syn::parenthesized!(content in input);
+    let args =
+        content.parse_terminated::<AttrArg, syn::Token![,]>(AttrArg::parse)?;
+    Ok(args.into_iter().collect())
+}
+
+struct AttrArgs(Vec<AttrArg>);
+
+impl Parse for AttrArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        parse_parenthesized(input).map(AttrArgs)
+    }
+}
+
+// Parses the arguments of an attribute like `#[flag(desc = "...", short = 'f')]`.
+pub(super) fn parse_attr_args(
+    attr: &syn::Attribute,
+) -> syn::Result<Vec<AttrArg>> {
+    syn::parse2::<AttrArgs>(attr.tts.clone())
+        .map(|args| args.0)
+        .map_err(|err| {
+            syn::Error::new(err.span(), "Unexpected attribute syntax")
+        })
+}
@@ -0,0 +1,199 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::attr_args::{parse_attr_args, AttrArg, AttrValue};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+struct Variant {
+    ident: syn::Ident,
+    // The name written on the command-line.
+    name: String,
+    aliases: Vec<String>,
+}
+
+// Generates `FromArg` for an enum whose variants have no fields.  `ToArg` is
+// left to the blanket implementation over `Display`, which the enum may have.
+pub(crate) fn derive(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let data = match &ast.data {
+        syn::Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "FromArg can only be derived for enums",
+            ));
+        }
+    };
+
+    let mut ignore_case = false;
+    for arg in arg_attrs(&ast.attrs)? {
+        match arg.value {
+            AttrValue::Word if arg.name == "ignore_case" => ignore_case = true,
+            _ => return Err(unknown_attr(&arg)),
+        }
+    }
+
+    let variants = data
+        .variants
+        .iter()
+        .map(extract_variant)
+        .collect::<syn::Result<Vec<Variant>>>()?;
+    check_duplicates(&variants, ignore_case)?;
+
+    let name = &ast.ident;
+    let normalize = |s: &str| {
+        if ignore_case {
+            s.to_lowercase()
+        } else {
+            String::from(s)
+        }
+    };
+    let parse_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let patterns = std::iter::once(&variant.name)
+            .chain(variant.aliases.iter())
+            .map(|s| normalize(s));
+        quote! {
+            #(#patterns)|* => Ok(#name::#ident),
+        }
+    });
+    let value = if ignore_case {
+        quote!(value.to_lowercase())
+    } else {
+        quote!(value)
+    };
+    let names = variants.iter().map(|variant| &variant.name);
+    let choices = names.clone();
+
+    Ok(quote! {
+        impl ctflag::FromArg for #name {
+            fn from_arg(value: &str) -> ctflag::FromArgResult<Self> {
+                match &*#value {
+                    #(#parse_arms)*
+                    _ => Err(ctflag::FromArgError::with_message(
+                        ctflag::internal::expected_one_of(
+                            value, &[#(#choices),*]))),
+                }
+            }
+
+            fn possible_values() -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+        }
+    })
+}
+
+fn extract_variant(variant: &syn::Variant) -> syn::Result<Variant> {
+    if !matches!(variant.fields, syn::Fields::Unit) {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "FromArg can only be derived for enums without fields",
+        ));
+    }
+    let mut name = kebab_case(&variant.ident.to_string());
+    let mut aliases = Vec::new();
+    for arg in arg_attrs(&variant.attrs)? {
+        if arg.name == "rename" {
+            name = parse_str(&arg)?;
+        } else if arg.name == "alias" {
+            aliases.push(parse_str(&arg)?);
+        } else {
+            return Err(unknown_attr(&arg));
+        }
+    }
+    Ok(Variant {
+        ident: variant.ident.clone(),
+        name,
+        aliases,
+    })
+}
+
+fn arg_attrs(attrs: &[syn::Attribute]) -> syn::Result<Vec<AttrArg>> {
+    let mut args = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("arg")) {
+        args.extend(parse_attr_args(attr)?);
+    }
+    Ok(args)
+}
+
+fn parse_str(arg: &AttrArg) -> syn::Result<String> {
+    match arg.expr()? {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Ok(lit.value()),
+        expr => Err(syn::Error::new_spanned(
+            expr,
+            format!("{} must be a string literal", arg.name),
+        )),
+    }
+}
+
+fn unknown_attr(arg: &AttrArg) -> syn::Error {
+    syn::Error::new_spanned(
+        &arg.name,
+        format!("Unknown arg attribute '{}'", arg.name),
+    )
+}
+
+// Every name and alias must select a single variant.
+fn check_duplicates(
+    variants: &[Variant],
+    ignore_case: bool,
+) -> syn::Result<()> {
+    let mut seen: Vec<String> = Vec::new();
+    for variant in variants {
+        for name in std::iter::once(&variant.name).chain(&variant.aliases) {
+            let key = if ignore_case {
+                name.to_lowercase()
+            } else {
+                name.clone()
+            };
+            if seen.contains(&key) {
+                return Err(syn::Error::new_spanned(
+                    &variant.ident,
+                    format!("Duplicate value \"{}\"", name),
+                ));
+            }
+            seen.push(key);
+        }
+    }
+    Ok(())
+}
+
+// Converts a CamelCase variant name to kebab-case, keeping acronyms
+// together: `HttpServer` and `HTTPServer` both become `http-server`.
+fn kebab_case(ident: &str) -> String {
+    let chars = ident.chars().collect::<Vec<char>>();
+    let mut buf = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower =
+                chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                buf.push('-');
+            }
+        }
+        if c == '_' {
+            buf.push('-');
+        } else {
+            buf.extend(c.to_lowercase());
+        }
+    }
+    buf
+}
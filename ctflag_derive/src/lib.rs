@@ -16,13 +16,13 @@
 
 extern crate proc_macro;
 
-mod string_builder;
+mod attr_args;
+mod from_arg;
 
+use attr_args::{parse_attr_args, AttrArg, AttrValue};
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned, ToTokens};
-use string_builder::StringBuilder;
 use syn::spanned::Spanned;
-use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Default)]
 struct Attrs {
@@ -30,6 +30,51 @@ struct Attrs {
     placeholder: Option<String>,
     default_value: Option<syn::Lit>,
     short_name: Option<syn::LitChar>,
+    group: Option<syn::LitStr>,
+    completer: Option<syn::Path>,
+    choices: Option<Vec<syn::LitStr>>,
+    validator: Option<syn::Path>,
+    conflicts_with: Vec<syn::LitStr>,
+    requires: Vec<syn::LitStr>,
+    required_unless: Vec<syn::LitStr>,
+    // Pairs of a flag name and the value that makes this flag required.
+    required_if: Vec<(syn::LitStr, syn::LitStr)>,
+    min: Option<Bound>,
+    max: Option<Bound>,
+    // Whether the bounds were given as `range = min..=max`.
+    has_range: bool,
+    // Whether the value is parsed with `FromArgOs`, set with `#[flag(os)]`.
+    os: bool,
+}
+
+// A bound of `#[flag(min = ..., max = ...)]`, which must be a number.
+#[derive(Clone)]
+struct Bound {
+    expr: syn::Expr,
+    value: f64,
+    text: String,
+    is_int: bool,
+}
+
+#[derive(Default)]
+struct StructAttrs {
+    groups: Vec<Group>,
+    about: Option<String>,
+    validator: Option<syn::Path>,
+    exclusive: Vec<Exclusive>,
+}
+
+struct Group {
+    name: syn::LitStr,
+    description: Option<String>,
+}
+
+// Flags of which at most one may be set, declared with
+// `#[flags(at_most_one_of = [...])]`, or exactly one with `one_of`.
+struct Exclusive {
+    attr: syn::Ident,
+    flags: Vec<syn::LitStr>,
+    required: bool,
 }
 
 enum FlagType {
@@ -46,15 +91,18 @@ struct Flag {
     flag_type: FlagType,
     ty: syn::Type,
     attrs: Attrs,
+    // Whether the value, or the value of the `Option`, has a type parameter
+    // of the struct anywhere in its type.
+    generic: bool,
 }
 
 impl Flag {
     fn placeholder(&self) -> Option<&str> {
-        self.attrs.placeholder.as_ref().map(String::as_str)
+        self.attrs.placeholder.as_deref()
     }
 
     fn description(&self) -> Option<&str> {
-        self.attrs.description.as_ref().map(String::as_str)
+        self.attrs.description.as_deref()
     }
 
     fn default_value(&self) -> Option<&syn::Lit> {
@@ -64,14 +112,53 @@ impl Flag {
     fn short_name(&self) -> Option<&syn::LitChar> {
         self.attrs.short_name.as_ref()
     }
+
+    fn group(&self) -> Option<&syn::LitStr> {
+        self.attrs.group.as_ref()
+    }
+
+    fn completer(&self) -> Option<&syn::Path> {
+        self.attrs.completer.as_ref()
+    }
+
+    fn choices(&self) -> Option<&[syn::LitStr]> {
+        self.attrs.choices.as_deref()
+    }
+
+    fn has_bounds(&self) -> bool {
+        self.attrs.min.is_some() || self.attrs.max.is_some()
+    }
+
+    // Returns true if the field is a `&str` borrowed from the arguments.
+    fn borrows(&self) -> bool {
+        let ty = extract_option_param_type(&self.ty)
+            .unwrap_or_else(|| self.ty.clone());
+        is_str_ref(&ty)
+    }
+
+    // Returns true if the value of the flag is passed as an `OsString` to
+    // `FromArgOs`, rather than validated as UTF-8 and passed to `FromArg`.
+    fn is_os(&self) -> bool {
+        let ty = extract_option_param_type(&self.ty)
+            .unwrap_or_else(|| self.ty.clone());
+        self.attrs.os || is_os_string(&ty)
+    }
 }
 
-#[proc_macro_derive(Flags, attributes(flag))]
+#[proc_macro_derive(Flags, attributes(flag, flags))]
 pub fn flag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as syn::DeriveInput);
     let name = &ast.ident;
-    match collect_flags(&ast) {
-        Ok(flags) => {
+    let parsed = collect_struct_attrs(&ast).and_then(|struct_attrs| {
+        let flags = collect_flags(&ast)?;
+        check_groups(&struct_attrs, &flags)?;
+        check_relations(&struct_attrs, &flags)?;
+        Ok((struct_attrs, flags))
+    });
+    let (impl_generics, ty_generics, where_clause) =
+        ast.generics.split_for_impl();
+    match parsed {
+        Ok((struct_attrs, flags)) => {
             if flags.is_empty() {
                 return proc_macro::TokenStream::from(
                     quote_spanned! {name.span()=>
@@ -80,46 +167,163 @@ pub fn flag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 );
             }
 
-            let temp_vars = flags.iter().map(generate_temp_vars);
-            let field_parsing = flags.iter().map(generate_field_parsing);
-            let field_assign = flags.iter().map(generate_field_assign);
-            let description = generate_description(&flags);
-            let expanded = quote! {
-                impl ctflag::Flags for #name {
-                    fn from_args<T>(args: T) -> ctflag::Result<(Self, Vec<String>)>
-                    where T: IntoIterator<Item = String> {
-                        #(#temp_vars)*
-                        let mut rest_args = Vec::<String>::new();
-                        // Skip the first arg (program name) and pass it through.
-                        let mut args = args.into_iter();
-                        args.next().map(|arg| rest_args.push(arg));
-                        let mut iter = ctflag::internal::FlagIterator::from_args(args);
-                        while let Some(arg) = iter.next() {
-                            use ctflag::internal::Arg;
-                            match arg {
-                                Arg::Arg(arg) => rest_args.push(arg),
-                                Arg::Flag(flag) => {
-                                    let arg_name = flag.key;
-                                    let arg_value = flag.val;
-                                    match arg_name.as_str() {
-                                        #(#field_parsing ,)*
-                                        _ => {
-                                            Err(ctflag::FlagError::UnrecognizedArg(
-                                                arg_name))?;
-                                        }
+            let temp_vars =
+                flags.iter().map(generate_temp_vars).collect::<Vec<_>>();
+            let field_parsing =
+                flags.iter().map(generate_field_parsing).collect::<Vec<_>>();
+            let field_assign =
+                flags.iter().map(generate_field_assign).collect::<Vec<_>>();
+            let flag_infos = flags.iter().map(|flag| {
+                generate_flag_info(
+                    flag,
+                    &conflicts_of(flag, &flags, &struct_attrs),
+                )
+            });
+            let check_relations =
+                generate_relation_checks(&flags, &struct_attrs);
+            let validate_defaults =
+                flags.iter().filter_map(generate_default_validation);
+            let unset_bools = flags.iter().filter_map(generate_unset_bool);
+            let flag_names = flags.iter().map(|flag| flag.name.to_string());
+            let group_infos =
+                struct_attrs.groups.iter().map(generate_group_info);
+            let validate_flags =
+                struct_attrs.validator.as_ref().map(|validator| {
+                    quote! {
+                        ctflag::internal::validate_flags(&flags, #validator)
+                            .map_err(ctflag::FlagErrors::from)?;
+                    }
+                });
+            let about = struct_attrs.about.as_ref().map(|about| {
+                quote! {
+                    fn about() -> Option<&'static str> {
+                        Some(#about)
+                    }
+                }
+            });
+            // The same parsing code is used for owned and borrowed arguments,
+            // with the differences hidden behind `ctflag::internal::ArgValue`.
+            let parse_body = quote! {
+                let mut errors = ctflag::internal::Errors::new(fail_fast);
+                #(#temp_vars)*
+                let mut rest_args = Vec::new();
+                let mut seen_flags = Vec::<(&'static str, usize)>::new();
+                // Skip the first arg (program name) and pass it through.
+                let mut args = args.into_iter();
+                args.next().map(|arg| rest_args.push(arg));
+                let mut iter = ctflag::internal::FlagIterator::from_args(args);
+                while let Some(arg) = iter.next() {
+                    match arg {
+                        ctflag::internal::Arg::Arg(arg) => rest_args.push(arg),
+                        ctflag::internal::Arg::Flag(flag) => {
+                            let arg_index = iter.index();
+                            let arg_name = flag.key;
+                            let arg_value = flag.val;
+                            // Errors are returned from a closure so that
+                            // the index of the flag can be added to them.
+                            let result = (|| -> ctflag::Result<()> {
+                                match std::convert::AsRef::<str>::as_ref(&arg_name) {
+                                    #(#field_parsing ,)*
+                                    _ => {
+                                        Err(ctflag::internal::unrecognized_arg(
+                                            arg_name.into(), [#(#flag_names),*]))?;
                                     }
                                 }
-                            }
+                                Ok(())
+                            })();
+                            errors.check(result.map_err(|err| {
+                                ctflag::internal::at_index(err, arg_index)
+                            }))?;
+                        }
+                    }
+                }
+                #(#validate_defaults)*
+                #(#unset_bools)*
+                #check_relations
+                errors.finish()?;
+                let flags = #name {
+                    #(#field_assign),*
+                };
+                #validate_flags
+                Ok((flags, rest_args))
+            };
+            // Flags compared by `required_if` are converted back to text.
+            let compared = flags
+                .iter()
+                .flat_map(|flag| &flag.attrs.required_if)
+                .map(|(other, _)| other.value())
+                .collect::<Vec<String>>();
+            let mut generics = ast.generics.clone();
+            add_flag_bounds(&mut generics, &flags, |flag| {
+                compared.contains(&flag.name.to_string())
+            });
+            let borrowed_impl = {
+                let lifetime = syn::Lifetime::new("'ctflag", Span::call_site());
+                let mut generics = generics.clone();
+                add_args_lifetime(&mut generics, &lifetime);
+                let (impl_generics, _, where_clause) =
+                    generics.split_for_impl();
+                quote! {
+                    impl #impl_generics ctflag::BorrowedFlags<#lifetime> for #name #ty_generics
+                    #where_clause {
+                        fn parse_str_args<CtflagArgs>(
+                            args: CtflagArgs,
+                            fail_fast: bool,
+                        ) -> std::result::Result<(Self, Vec<&#lifetime str>), ctflag::FlagErrors>
+                        where CtflagArgs: IntoIterator<Item = &#lifetime str> {
+                            #parse_body
                         }
-                        Ok((#name {
-                            #(#field_assign),*
-                        }, rest_args))
+                    }
+                }
+            };
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            let describe_impl = quote! {
+                impl #impl_generics ctflag::DescribeFlags for #name #ty_generics #where_clause {
+                    fn metadata() -> Vec<ctflag::FlagInfo<'static>> {
+                        vec![#(#flag_infos),*]
+                    }
+
+                    fn groups() -> Vec<ctflag::GroupInfo<'static>> {
+                        vec![#(#group_infos),*]
+                    }
+
+                    #about
+                }
+            };
+            // Borrowed fields can only be parsed from borrowed arguments, so
+            // structs with a lifetime don't implement `Flags`.
+            if ast.generics.lifetimes().next().is_some() {
+                return proc_macro::TokenStream::from(quote! {
+                    #describe_impl
+
+                    #borrowed_impl
+                });
+            }
+            let expanded = quote! {
+                impl #impl_generics ctflag::Flags for #name #ty_generics #where_clause {
+                    fn from_args<CtflagArgs>(args: CtflagArgs) -> ctflag::Result<(Self, Vec<String>)>
+                    where CtflagArgs: IntoIterator<Item = String> {
+                        let args = args.into_iter().map(std::ffi::OsString::from);
+                        Self::parse_args_os(args, true)
+                            .map(ctflag::internal::string_args)
+                            .map_err(ctflag::internal::first_error)
                     }
 
-                    fn description() -> String {
-                        #description
+                    fn parse_args_os<CtflagArgs>(
+                        args: CtflagArgs,
+                        fail_fast: bool,
+                    ) -> std::result::Result<
+                        (Self, Vec<std::ffi::OsString>),
+                        ctflag::FlagErrors,
+                    >
+                    where CtflagArgs: IntoIterator<Item = std::ffi::OsString> {
+                        #parse_body
                     }
                 }
+
+                #describe_impl
+
+                #borrowed_impl
             };
             proc_macro::TokenStream::from(expanded)
         }
@@ -128,12 +332,15 @@ pub fn flag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             proc_macro::TokenStream::from(quote_spanned! {err.span()=>
                 #compile_error
 
-                impl ctflag::Flags for #name {
-                    fn from_args(args: std::env::Args) -> ctflag::Result<(Self, Vec<String>)> {
+                impl #impl_generics ctflag::Flags for #name #ty_generics #where_clause {
+                    fn from_args<CtflagArgs>(_args: CtflagArgs) -> ctflag::Result<(Self, Vec<String>)>
+                    where CtflagArgs: IntoIterator<Item = String> {
                         panic!("Unimplemented");
                     }
+                }
 
-                    fn description() -> String {
+                impl #impl_generics ctflag::DescribeFlags for #name #ty_generics #where_clause {
+                    fn metadata() -> Vec<ctflag::FlagInfo<'static>> {
                         panic!("Unimplemented");
                     }
                 }
@@ -142,17 +349,194 @@ pub fn flag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     }
 }
 
+// Adds the bounds that the generated code needs on the type parameters of
+// the struct.  A field whose type uses a type parameter, like `T` or
+// `Option<Id<T>>`, needs its value type to implement `FromArg`, or
+// `FromArgOs`, and `Default` unless the field has a default value.  `needs_to_arg` returns true for fields that are also
+// converted back to text.
+fn add_flag_bounds<F>(
+    generics: &mut syn::Generics,
+    flags: &[Flag],
+    needs_to_arg: F,
+) where
+    F: Fn(&Flag) -> bool,
+{
+    let where_clause = generics.make_where_clause();
+    for flag in flags.iter().filter(|flag| flag.generic) {
+        let is_option = matches!(flag.flag_type, FlagType::Option);
+        let value_ty = extract_option_param_type(&flag.ty)
+            .unwrap_or_else(|| flag.ty.clone());
+        let from_arg = if flag.is_os() {
+            quote!(ctflag::FromArgOs)
+        } else {
+            quote!(ctflag::FromArg)
+        };
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#value_ty: #from_arg));
+        if !is_option && flag.default_value().is_none() {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#value_ty: Default));
+        }
+        if needs_to_arg(flag) {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#value_ty: ctflag::ToArg));
+        }
+    }
+}
+
+// Adds `lifetime`, the lifetime of the arguments given to `BorrowedFlags`,
+// which must outlive every lifetime of the struct.
+fn add_args_lifetime(generics: &mut syn::Generics, lifetime: &syn::Lifetime) {
+    let lifetimes = generics
+        .lifetimes()
+        .map(|def| def.lifetime.clone())
+        .collect::<Vec<syn::Lifetime>>();
+    generics.params.insert(
+        0,
+        syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
+    );
+    let where_clause = generics.make_where_clause();
+    for other in lifetimes {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#lifetime: #other));
+    }
+}
+
+#[proc_macro_derive(FromArg, attributes(arg))]
+pub fn from_arg_derive(
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    match from_arg::derive(&ast) {
+        Ok(expanded) => proc_macro::TokenStream::from(expanded),
+        Err(err) => proc_macro::TokenStream::from(err.to_compile_error()),
+    }
+}
+
+#[proc_macro_derive(ToArgs, attributes(flag, flags))]
+pub fn to_args_derive(
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    let name = &ast.ident;
+    match collect_flags(&ast) {
+        Ok(flags) => {
+            let non_default_args =
+                flags.iter().map(|flag| generate_to_arg(flag, false));
+            let all_args = flags.iter().map(|flag| generate_to_arg(flag, true));
+            let mut generics = ast.generics.clone();
+            add_flag_bounds(&mut generics, &flags, |_| true);
+            let (impl_generics, ty_generics, where_clause) =
+                generics.split_for_impl();
+            let expanded = quote! {
+                impl #impl_generics ctflag::ToArgs for #name #ty_generics #where_clause {
+                    fn to_args(&self) -> Vec<String> {
+                        let mut args = Vec::<String>::new();
+                        #(#non_default_args)*
+                        args
+                    }
+
+                    fn to_all_args(&self) -> Vec<String> {
+                        let mut args = Vec::<String>::new();
+                        #(#all_args)*
+                        args
+                    }
+                }
+            };
+            proc_macro::TokenStream::from(expanded)
+        }
+        Err(err) => proc_macro::TokenStream::from(err.to_compile_error()),
+    }
+}
+
+// Pushes the flag as `--name=value` onto `args`.  Unless `all` is set, flags
+// holding their default value are left out.
+fn generate_to_arg(flag: &Flag, all: bool) -> TokenStream {
+    let name = &flag.name;
+    let prefix = format!("--{}=", name);
+    if let FlagType::Option = flag.flag_type {
+        return quote! {
+            if let Some(value) = &self.#name {
+                args.push(format!("{}{}", #prefix, ctflag::ToArg::to_arg(value)));
+            }
+        };
+    }
+    let value = quote!(ctflag::ToArg::to_arg(&self.#name));
+    if all {
+        return quote! {
+            args.push(format!("{}{}", #prefix, #value));
+        };
+    }
+    let ty = &flag.ty;
+    if let Some(default) = try_parse_default(flag) {
+        // A default that can't be parsed is never held.
+        return quote! {
+            {
+                let value = #value;
+                let default = #default
+                    .ok()
+                    .map(|default: #ty| ctflag::ToArg::to_arg(&default));
+                if default.as_ref() != Some(&value) {
+                    args.push(format!("{}{}", #prefix, value));
+                }
+            }
+        };
+    }
+    let default = parse_default(flag);
+    quote! {
+        {
+            let value = #value;
+            let default: #ty = #default;
+            if value != ctflag::ToArg::to_arg(&default) {
+                args.push(format!("{}{}", #prefix, value));
+            }
+        }
+    }
+}
+
 fn generate_temp_vars(flag: &Flag) -> TokenStream {
     let name = &flag.name;
     let name_temp_var =
         syn::Ident::new(&format!("parsed_{}", name), name.span());
     let ty = &flag.ty;
-    let rhs = parse_default(flag);
+    let rhs = match try_parse_default(flag) {
+        Some(default) => {
+            let name_lit = name.to_string();
+            let lit = flag.default_value();
+            quote! {
+                #default.map_err(|err| {
+                    ctflag::FlagErrors::from(ctflag::internal::parse_error(
+                        #name_lit, stringify!(#ty), #lit, err))
+                })?
+            }
+        }
+        None => parse_default(flag),
+    };
     quote_spanned! {name.span()=>
         let mut #name_temp_var : #ty = #rhs;
     }
 }
 
+// The default of a field parsed with `FromArg` or `FromArgOs` is parsed at
+// runtime, which can fail, so it is reported like a bad argument instead of
+// being unwrapped.  Returns the `Result` of parsing it, if the field has one.
+fn try_parse_default(flag: &Flag) -> Option<TokenStream> {
+    if !matches!(flag.flag_type, FlagType::Other) {
+        return None;
+    }
+    match flag.default_value()? {
+        syn::Lit::Str(lit) if flag.is_os() => Some(quote! {
+            ctflag::FromArgOs::from_arg_os(std::ffi::OsStr::new(#lit))
+        }),
+        syn::Lit::Str(lit) => Some(quote!(ctflag::FromArg::from_arg(#lit))),
+        _ => None,
+    }
+}
+
 fn parse_default(flag: &Flag) -> TokenStream {
     if flag.default_value().is_none() {
         return quote_spanned!(flag.ty.span()=> Default::default());
@@ -164,6 +548,7 @@ fn parse_default(flag: &Flag) -> TokenStream {
             // with type mismatches.
             default.clone().into_token_stream()
         }
+        FlagType::String if flag.borrows() => default.into_token_stream(),
         FlagType::String => quote!(String::from(#default)),
         FlagType::Option => {
             // Option implies no default.
@@ -172,13 +557,8 @@ fn parse_default(flag: &Flag) -> TokenStream {
             }
         }
         FlagType::Other => {
-            if let syn::Lit::Str(lit) = default {
-                quote! {
-                    ctflag::FromArg::from_arg(#lit).expect("failed to parse default flag value")
-                }
-            } else {
-                quote_spanned!(default.span()=> compile_error!("Expected string literal"))
-            }
+            // String literals are parsed by `try_parse_default`.
+            quote_spanned!(default.span()=> compile_error!("Expected string literal"))
         }
     }
 }
@@ -193,12 +573,17 @@ fn generate_field_assign(flag: &Flag) -> TokenStream {
 }
 
 fn collect_flags(ast: &syn::DeriveInput) -> syn::Result<Vec<Flag>> {
+    let params = ast
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<syn::Ident>>();
     if let syn::Data::Struct(struct_data) = &ast.data {
         if let syn::Fields::Named(fields) = &struct_data.fields {
             return fields
                 .named
                 .iter()
-                .map(extract_flag)
+                .map(|field| extract_flag(field, &params))
                 .collect::<syn::Result<Vec<Flag>>>();
         }
     }
@@ -208,32 +593,420 @@ fn collect_flags(ast: &syn::DeriveInput) -> syn::Result<Vec<Flag>> {
     ))
 }
 
-fn extract_flag(field: &syn::Field) -> syn::Result<Flag> {
-    // Parse the attributes into syn::Meta types.
-    let attr_metas = field
-        .attrs
+fn collect_struct_attrs(ast: &syn::DeriveInput) -> syn::Result<StructAttrs> {
+    let mut attrs = StructAttrs::default();
+    for attr in ast.attrs.iter().filter(|a| a.path.is_ident("flags")) {
+        for arg in parse_attr_args(attr)? {
+            extract_struct_attr(&arg, &mut attrs)?;
+        }
+    }
+    attrs.about = extract_doc_comment(&ast.attrs);
+    Ok(attrs)
+}
+
+// Joins the lines of a `///` doc comment, which are `#[doc = "..."]`
+// attributes by the time the derive sees them.
+fn extract_doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines = attrs
         .iter()
-        .map(syn::Attribute::parse_meta)
-        .collect::<syn::Result<Vec<syn::Meta>>>()?;
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(line),
+                ..
+            })) => Some(line.value()),
+            _ => None,
+        })
+        .map(|line| match line.strip_prefix(' ') {
+            Some(line) => String::from(line),
+            None => line,
+        })
+        .collect::<Vec<String>>();
+    let doc = lines.join("\n");
+    let doc = doc.trim();
+    if doc.is_empty() {
+        None
+    } else {
+        Some(String::from(doc))
+    }
+}
 
-    let flag_ident = syn::Ident::new("flag", Span::call_site());
+fn extract_struct_attr(
+    arg: &AttrArg,
+    attrs: &mut StructAttrs,
+) -> syn::Result<()> {
+    match &arg.value {
+        AttrValue::List(args) if arg.name == "group" => {
+            attrs.groups.push(parse_struct_attr_group(arg, args)?);
+        }
+        AttrValue::Expr(expr) if arg.name == "validate" => {
+            attrs.validator = Some(parse_flag_attr_fn_path(expr)?);
+        }
+        AttrValue::Expr(expr)
+            if arg.name == "one_of" || arg.name == "at_most_one_of" =>
+        {
+            attrs.exclusive.push(Exclusive {
+                attr: arg.name.clone(),
+                flags: parse_flag_attr_names(expr)?,
+                required: arg.name == "one_of",
+            });
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &arg.name,
+                format!("Unknown flags attribute '{}'", arg.name),
+            ));
+        }
+    }
+    Ok(())
+}
 
-    // Find all 'flag' attributes and extract known attributes from them.
-    let attrs: Attrs = attr_metas
+fn parse_struct_attr_group(
+    group: &AttrArg,
+    args: &[AttrArg],
+) -> syn::Result<Group> {
+    let mut name = None;
+    let mut description = None;
+    for arg in args {
+        if arg.name == "name" {
+            name = Some(parse_flag_attr_group(arg.expr()?)?);
+        } else if arg.name == "desc" {
+            description = Some(parse_flag_attr_description(arg.expr()?)?);
+        } else {
+            return Err(syn::Error::new_spanned(
+                &arg.name,
+                format!("Unknown group attribute '{}'", arg.name),
+            ));
+        }
+    }
+    match name {
+        Some(name) => Ok(Group { name, description }),
+        None => Err(syn::Error::new_spanned(
+            &group.name,
+            "Group must have a name",
+        )),
+    }
+}
+
+// Every declared group must be unique and contain at least one flag.
+fn check_groups(struct_attrs: &StructAttrs, flags: &[Flag]) -> syn::Result<()> {
+    for (i, group) in struct_attrs.groups.iter().enumerate() {
+        let name = group.name.value();
+        if struct_attrs.groups[..i]
+            .iter()
+            .any(|other| other.name.value() == name)
+        {
+            return Err(syn::Error::new_spanned(
+                &group.name,
+                format!("Group '{}' is declared more than once", name),
+            ));
+        }
+        if !flags.iter().any(|flag| {
+            flag.group().map(syn::LitStr::value) == Some(name.clone())
+        }) {
+            return Err(syn::Error::new_spanned(
+                &group.name,
+                format!("Group '{}' has no flags", name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Flags named by `conflicts_with`, `requires`, `required_unless`,
+// `required_if`, `one_of` and `at_most_one_of` must exist, and must not be the
+// flag itself.
+fn check_relations(
+    struct_attrs: &StructAttrs,
+    flags: &[Flag],
+) -> syn::Result<()> {
+    let check_name = |name: &syn::LitStr| {
+        if flags.iter().any(|flag| flag.name == name.value()) {
+            Ok(())
+        } else {
+            Err(syn::Error::new_spanned(
+                name,
+                format!("Unknown flag '{}'", name.value()),
+            ))
+        }
+    };
+    for flag in flags {
+        let names = flag
+            .attrs
+            .conflicts_with
+            .iter()
+            .chain(&flag.attrs.requires)
+            .chain(&flag.attrs.required_unless)
+            .chain(flag.attrs.required_if.iter().map(|(name, _)| name));
+        for name in names {
+            check_name(name)?;
+            if flag.name == name.value() {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "Flag must not refer to itself",
+                ));
+            }
+        }
+    }
+    for exclusive in &struct_attrs.exclusive {
+        if exclusive.flags.len() < 2 {
+            return Err(syn::Error::new_spanned(
+                &exclusive.attr,
+                format!("'{}' must list at least two flags", exclusive.attr),
+            ));
+        }
+        for name in &exclusive.flags {
+            check_name(name)?;
+        }
+    }
+    Ok(())
+}
+
+// The names of the flags that cannot be set together with `flag`, in the
+// order they are declared.
+fn conflicts_of(
+    flag: &Flag,
+    flags: &[Flag],
+    struct_attrs: &StructAttrs,
+) -> Vec<String> {
+    let conflicts = |a: &Flag, b: &Flag| names_flag(&a.attrs.conflicts_with, b);
+    let exclusive = |a: &Flag, b: &Flag| {
+        struct_attrs.exclusive.iter().any(|exclusive| {
+            names_flag(&exclusive.flags, a) && names_flag(&exclusive.flags, b)
+        })
+    };
+    flags
         .iter()
-        .filter(|m| m.name() == flag_ident)
-        .map(extract_flag_attrs)
-        .next()
-        .unwrap_or_else(|| Ok(Attrs::default()))?;
+        .filter(|other| other.name != flag.name)
+        .filter(|other| {
+            conflicts(flag, other)
+                || conflicts(other, flag)
+                || exclusive(flag, other)
+        })
+        .map(|other| other.name.to_string())
+        .collect()
+}
+
+fn names_flag(names: &[syn::LitStr], flag: &Flag) -> bool {
+    names.iter().any(|name| flag.name == name.value())
+}
 
-    Ok(Flag {
+// Checks the flags set on the command-line against `conflicts_with`,
+// `requires` and the exclusive groups, once all of them are parsed.
+fn generate_relation_checks(
+    flags: &[Flag],
+    struct_attrs: &StructAttrs,
+) -> TokenStream {
+    let mut checks = Vec::<TokenStream>::new();
+    for flag in flags {
+        let name = flag.name.to_string();
+        for other in &flag.attrs.conflicts_with {
+            checks.push(quote! {
+                ctflag::internal::check_conflict(&seen_flags, #name, #other)
+            });
+        }
+    }
+    for exclusive in &struct_attrs.exclusive {
+        let names = &exclusive.flags;
+        let required = exclusive.required;
+        checks.push(quote! {
+            ctflag::internal::check_one_of(&seen_flags, &[#(#names),*], #required)
+        });
+    }
+    for flag in flags {
+        let name = flag.name.to_string();
+        for required in &flag.attrs.requires {
+            checks.push(quote! {
+                ctflag::internal::check_requires(&seen_flags, #name, #required)
+            });
+        }
+        let unless = &flag.attrs.required_unless;
+        if !unless.is_empty() {
+            checks.push(quote! {
+                ctflag::internal::check_required_unless(
+                    &seen_flags, #name, &[#(#unless),*])
+            });
+        }
+        for (other, value) in &flag.attrs.required_if {
+            let has_value = generate_has_value(flags, other, value);
+            checks.push(quote! {
+                ctflag::internal::check_required_if(
+                    &seen_flags, #name, #other, #value, #has_value)
+            });
+        }
+    }
+    quote! {
+        #(errors.check(#checks)?;)*
+    }
+}
+
+// Compares the parsed value of the flag named `name` with `value`, as text.
+fn generate_has_value(
+    flags: &[Flag],
+    name: &syn::LitStr,
+    value: &syn::LitStr,
+) -> TokenStream {
+    let flag = flags.iter().find(|flag| flag.name == name.value()).unwrap();
+    let temp_var =
+        syn::Ident::new(&format!("parsed_{}", flag.name), name.span());
+    match &flag.flag_type {
+        FlagType::Option => quote! {
+            #temp_var
+                .as_ref()
+                .map_or(false, |value| ctflag::ToArg::to_arg(value) == #value)
+        },
+        _ => quote! {
+            ctflag::ToArg::to_arg(&#temp_var) == #value
+        },
+    }
+}
+
+fn extract_flag(
+    field: &syn::Field,
+    params: &[syn::Ident],
+) -> syn::Result<Flag> {
+    // Find all 'flag' attributes and extract known attributes from them.
+    let mut attrs = Attrs::default();
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("flag")) {
+        for arg in parse_attr_args(attr)? {
+            extract_flag_attr(&arg, &mut attrs)?;
+        }
+    }
+
+    let value_ty = extract_option_param_type(&field.ty)
+        .unwrap_or_else(|| field.ty.clone());
+    let generic = mentions_param(value_ty.into_token_stream(), params);
+    let flag = Flag {
         name: field.ident.as_ref().unwrap().clone(),
         flag_type: extract_flag_type(&field.ty),
         ty: field.ty.clone(),
-        attrs: attrs,
+        attrs,
+        generic,
+    };
+    check_choices(&flag)?;
+    check_bounds(&flag)?;
+    Ok(flag)
+}
+
+// Returns true if one of `params` appears anywhere in `tokens`, as in `T`,
+// `Id<T>` or `[T; 2]`.
+fn mentions_param(tokens: TokenStream, params: &[syn::Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => params.contains(&ident),
+        proc_macro2::TokenTree::Group(group) => {
+            mentions_param(group.stream(), params)
+        }
+        _ => false,
     })
 }
 
+// Bounds are only allowed for numbers, must be in order, and must contain
+// the default.
+fn check_bounds(flag: &Flag) -> syn::Result<()> {
+    if !flag.has_bounds() {
+        return Ok(());
+    }
+    let value_ty =
+        extract_option_param_type(&flag.ty).unwrap_or_else(|| flag.ty.clone());
+    let is_int = match extract_flag_type(&value_ty) {
+        FlagType::Int => true,
+        FlagType::Float => false,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &flag.ty,
+                "Bounds are only allowed with integer and float types",
+            ));
+        }
+    };
+    let bounds = flag.attrs.min.iter().chain(flag.attrs.max.iter());
+    for bound in bounds {
+        if is_int && !bound.is_int {
+            return Err(syn::Error::new_spanned(
+                &bound.expr,
+                "Bounds of integer flags must be integers",
+            ));
+        }
+    }
+    if let (Some(min), Some(max)) = (&flag.attrs.min, &flag.attrs.max) {
+        if min.value > max.value {
+            return Err(syn::Error::new_spanned(
+                &max.expr,
+                "Max must not be less than min",
+            ));
+        }
+    }
+    let default = match flag.default_value() {
+        Some(syn::Lit::Int(lit)) => Some(lit.value() as f64),
+        Some(syn::Lit::Float(lit)) => Some(lit.value()),
+        _ => None,
+    };
+    if let Some(default) = default {
+        let too_small = flag
+            .attrs
+            .min
+            .as_ref()
+            .is_some_and(|min| default < min.value);
+        let too_large = flag
+            .attrs
+            .max
+            .as_ref()
+            .is_some_and(|max| default > max.value);
+        if too_small || too_large {
+            return Err(syn::Error::new_spanned(
+                flag.default_value().unwrap(),
+                "Default must be within the bounds",
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The bound as a literal of the flag's type, so that integer bounds of float
+// flags type check.
+fn bound_tokens(bound: Option<&Bound>, is_float: bool) -> TokenStream {
+    match bound {
+        Some(bound) => {
+            let mut text = bound.text.clone();
+            if is_float && !text.contains('.') {
+                text.push_str(".0");
+            }
+            let expr = syn::parse_str::<syn::Expr>(&text).unwrap();
+            quote!(Some(#expr))
+        }
+        None => quote!(None),
+    }
+}
+
+// Choices are only checked for string flags, and the default must be one of
+// them.
+fn check_choices(flag: &Flag) -> syn::Result<()> {
+    let choices = match flag.choices() {
+        Some(choices) => choices,
+        None => return Ok(()),
+    };
+    let is_string = match &flag.flag_type {
+        FlagType::String => true,
+        FlagType::Option => extract_option_param_type(&flag.ty)
+            .is_some_and(|ty| is_string(&ty) || is_str_ref(&ty)),
+        _ => false,
+    };
+    if !is_string {
+        return Err(syn::Error::new_spanned(
+            &flag.ty,
+            "Choices are only allowed with String and &str types, or an Option of them",
+        ));
+    }
+    if let Some(syn::Lit::Str(def)) = flag.default_value() {
+        if !choices.iter().any(|choice| choice.value() == def.value()) {
+            return Err(syn::Error::new_spanned(
+                def,
+                "Default must be one of the choices",
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn extract_flag_type(ty: &syn::Type) -> FlagType {
     if is_bool(ty) {
         FlagType::Bool
@@ -241,9 +1014,9 @@ fn extract_flag_type(ty: &syn::Type) -> FlagType {
         FlagType::Int
     } else if is_float(ty) {
         FlagType::Float
-    } else if is_string(ty) {
+    } else if is_string(ty) || is_str_ref(ty) {
         FlagType::String
-    } else if let Some(_) = extract_option_param_type(ty) {
+    } else if extract_option_param_type(ty).is_some() {
         FlagType::Option
     } else {
         FlagType::Other
@@ -252,7 +1025,7 @@ fn extract_flag_type(ty: &syn::Type) -> FlagType {
 
 fn is_bool(ty: &syn::Type) -> bool {
     if let syn::Type::Path(p) = ty {
-        p.path.is_ident(syn::Ident::new("bool", Span::call_site()))
+        p.path.is_ident("bool")
     } else {
         false
     }
@@ -265,11 +1038,7 @@ fn is_int(ty: &syn::Type) -> bool {
             "usize",
         ]
         .iter()
-        .filter(|ident| {
-            p.path.is_ident(syn::Ident::new(ident, Span::call_site()))
-        })
-        .next()
-        .is_some()
+        .any(|ident| p.path.is_ident(syn::Ident::new(ident, Span::call_site())))
     } else {
         false
     }
@@ -277,13 +1046,9 @@ fn is_int(ty: &syn::Type) -> bool {
 
 fn is_float(ty: &syn::Type) -> bool {
     if let syn::Type::Path(p) = ty {
-        ["f32", "f64"]
-            .iter()
-            .filter(|ident| {
-                p.path.is_ident(syn::Ident::new(ident, Span::call_site()))
-            })
-            .next()
-            .is_some()
+        ["f32", "f64"].iter().any(|ident| {
+            p.path.is_ident(syn::Ident::new(ident, Span::call_site()))
+        })
     } else {
         false
     }
@@ -298,6 +1063,28 @@ fn is_string(ty: &syn::Type) -> bool {
     }
 }
 
+// Returns true for `&str`, with any lifetime.
+fn is_str_ref(ty: &syn::Type) -> bool {
+    if let syn::Type::Reference(r) = ty {
+        if let syn::Type::Path(p) = &*r.elem {
+            return r.mutability.is_none() && p.path.is_ident("str");
+        }
+    }
+    false
+}
+
+// Returns true for `PathBuf` and `OsString`, which can hold any argument.
+fn is_os_string(ty: &syn::Type) -> bool {
+    if let syn::Type::Path(p) = ty {
+        p.path.segments.last().is_some_and(|segment| {
+            let ident = &segment.value().ident;
+            ident == "PathBuf" || ident == "OsString"
+        })
+    } else {
+        false
+    }
+}
+
 fn extract_option_param_type(ty: &syn::Type) -> Option<syn::Type> {
     if let syn::Type::Path(p) = ty {
         if p.path.leading_colon.is_none()
@@ -319,133 +1106,400 @@ fn extract_option_param_type(ty: &syn::Type) -> Option<syn::Type> {
     None
 }
 
-fn extract_flag_attrs(meta: &syn::Meta) -> syn::Result<Attrs> {
-    let mut attrs = Attrs::default();
-    if let syn::Meta::List(l) = meta {
-        for nested in &l.nested {
-            if let syn::NestedMeta::Meta(syn::Meta::NameValue(name_val)) =
-                nested
-            {
-                if name_val.ident == syn::Ident::new("desc", Span::call_site())
-                {
-                    attrs.description =
-                        Some(parse_flag_attr_description(&name_val.lit)?);
-                } else if name_val.ident
-                    == syn::Ident::new("placeholder", Span::call_site())
-                {
-                    attrs.placeholder =
-                        Some(parse_flag_attr_placeholder(&name_val.lit)?);
-                } else if name_val.ident
-                    == syn::Ident::new("default", Span::call_site())
-                {
-                    attrs.default_value = Some(name_val.lit.clone());
-                } else if name_val.ident
-                    == syn::Ident::new("short", Span::call_site())
-                {
-                    attrs.short_name =
-                        Some(parse_flag_attr_short_name(&name_val.lit)?);
-                } else {
-                    return Err(syn::Error::new_spanned(
-                        &name_val.ident,
-                        format!("Unknown flags attribute '{}'", name_val.ident),
-                    ));
-                }
-            } else {
+fn extract_flag_attr(arg: &AttrArg, attrs: &mut Attrs) -> syn::Result<()> {
+    let name = &arg.name;
+    if name == "desc" {
+        attrs.description = Some(parse_flag_attr_description(arg.expr()?)?);
+    } else if name == "placeholder" {
+        attrs.placeholder = Some(parse_flag_attr_placeholder(arg.expr()?)?);
+    } else if name == "default" {
+        attrs.default_value = Some(parse_flag_attr_default(arg.expr()?)?);
+    } else if name == "short" {
+        attrs.short_name = Some(parse_flag_attr_short_name(arg.expr()?)?);
+    } else if name == "group" {
+        attrs.group = Some(parse_flag_attr_group(arg.expr()?)?);
+    } else if name == "complete" {
+        attrs.completer = Some(parse_flag_attr_fn_path(arg.expr()?)?);
+    } else if *name == "choices" {
+        attrs.choices = Some(parse_flag_attr_choices(arg.expr()?)?);
+    } else if *name == "conflicts_with" {
+        attrs
+            .conflicts_with
+            .extend(parse_flag_attr_names(arg.expr()?)?);
+    } else if *name == "requires" {
+        attrs.requires.extend(parse_flag_attr_names(arg.expr()?)?);
+    } else if *name == "required_unless" {
+        attrs
+            .required_unless
+            .extend(parse_flag_attr_names(arg.expr()?)?);
+    } else if *name == "required_if" {
+        attrs
+            .required_if
+            .push(parse_flag_attr_condition(arg.expr()?)?);
+    } else if *name == "os" {
+        match arg.value {
+            AttrValue::Word => attrs.os = true,
+            _ => {
                 return Err(syn::Error::new_spanned(
-                    &l.nested,
-                    "Unexpected attribute syntax",
-                ));
+                    name,
+                    "Expected 'os' without a value",
+                ))
             }
         }
+    } else if *name == "validate" {
+        attrs.validator = Some(parse_flag_attr_fn_path(arg.expr()?)?);
+    } else if *name == "min" || *name == "max" {
+        if attrs.has_range {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Use either range or min and max",
+            ));
+        }
+        let bound = Some(parse_flag_attr_bound(arg.expr()?)?);
+        if *name == "min" {
+            attrs.min = bound;
+        } else {
+            attrs.max = bound;
+        }
+    } else if *name == "range" {
+        if attrs.min.is_some() || attrs.max.is_some() {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Use either range or min and max",
+            ));
+        }
+        let (min, max) = parse_flag_attr_range(arg.expr()?)?;
+        attrs.min = min;
+        attrs.max = max;
+        attrs.has_range = true;
     } else {
         return Err(syn::Error::new_spanned(
-            meta,
-            "Unexpected attribute syntax",
+            name,
+            format!("Unknown flags attribute '{}'", name),
         ));
     }
-    return Ok(attrs);
+    Ok(())
+}
+
+// Returns the literal of an attribute value like `desc = "..."`.
+fn attr_lit(expr: &syn::Expr) -> Option<&syn::Lit> {
+    match expr {
+        syn::Expr::Lit(expr_lit) => Some(&expr_lit.lit),
+        _ => None,
+    }
 }
 
-fn parse_flag_attr_description(literal: &syn::Lit) -> syn::Result<String> {
-    if let syn::Lit::Str(desc) = literal {
+fn parse_flag_attr_description(expr: &syn::Expr) -> syn::Result<String> {
+    if let Some(syn::Lit::Str(desc)) = attr_lit(expr) {
         Ok(desc.value())
     } else {
         Err(syn::Error::new_spanned(
-            literal,
+            expr,
             "Description must be a string literal",
         ))
     }
 }
 
-fn parse_flag_attr_placeholder(literal: &syn::Lit) -> syn::Result<String> {
-    if let syn::Lit::Str(val) = literal {
+fn parse_flag_attr_placeholder(expr: &syn::Expr) -> syn::Result<String> {
+    if let Some(syn::Lit::Str(val)) = attr_lit(expr) {
         Ok(val.value())
     } else {
         Err(syn::Error::new_spanned(
-            literal,
+            expr,
             "Placeholder must be a string literal",
         ))
     }
 }
 
-fn parse_flag_attr_short_name(literal: &syn::Lit) -> syn::Result<syn::LitChar> {
-    if let syn::Lit::Char(val) = literal {
+fn parse_flag_attr_default(expr: &syn::Expr) -> syn::Result<syn::Lit> {
+    if let Some(lit) = attr_lit(expr) {
+        Ok(lit.clone())
+    } else {
+        Err(syn::Error::new_spanned(expr, "Default must be a literal"))
+    }
+}
+
+fn parse_flag_attr_short_name(expr: &syn::Expr) -> syn::Result<syn::LitChar> {
+    if let Some(syn::Lit::Char(val)) = attr_lit(expr) {
         Ok(val.clone())
     } else {
         Err(syn::Error::new_spanned(
-            literal,
+            expr,
             "Short name must be a char literal",
         ))
     }
 }
 
+fn parse_flag_attr_group(expr: &syn::Expr) -> syn::Result<syn::LitStr> {
+    if let Some(syn::Lit::Str(val)) = attr_lit(expr) {
+        Ok(val.clone())
+    } else {
+        Err(syn::Error::new_spanned(
+            expr,
+            "Group must be a string literal",
+        ))
+    }
+}
+
+fn parse_flag_attr_choices(expr: &syn::Expr) -> syn::Result<Vec<syn::LitStr>> {
+    let err = || {
+        syn::Error::new_spanned(
+            expr,
+            "Choices must be an array of string literals",
+        )
+    };
+    let array = match expr {
+        syn::Expr::Array(array) if !array.elems.is_empty() => array,
+        _ => return Err(err()),
+    };
+    array
+        .elems
+        .iter()
+        .map(|elem| match attr_lit(elem) {
+            Some(syn::Lit::Str(choice)) => Ok(choice.clone()),
+            _ => Err(err()),
+        })
+        .collect()
+}
+
+// Accepts a single flag name or an array of them.
+fn parse_flag_attr_names(expr: &syn::Expr) -> syn::Result<Vec<syn::LitStr>> {
+    let err = || {
+        syn::Error::new_spanned(
+            expr,
+            "Expected a flag name or an array of flag names",
+        )
+    };
+    let elems = match expr {
+        syn::Expr::Array(array) if !array.elems.is_empty() => {
+            array.elems.iter().collect::<Vec<&syn::Expr>>()
+        }
+        syn::Expr::Lit(_) => vec![expr],
+        _ => return Err(err()),
+    };
+    elems
+        .into_iter()
+        .map(|elem| match attr_lit(elem) {
+            Some(syn::Lit::Str(name)) => Ok(name.clone()),
+            _ => Err(err()),
+        })
+        .collect()
+}
+
+// Accepts a `("flag", "value")` pair.
+fn parse_flag_attr_condition(
+    expr: &syn::Expr,
+) -> syn::Result<(syn::LitStr, syn::LitStr)> {
+    let err = || {
+        syn::Error::new_spanned(
+            expr,
+            "Expected a flag name and a value, like (\"flag\", \"value\")",
+        )
+    };
+    let elems = match expr {
+        syn::Expr::Tuple(tuple) if tuple.elems.len() == 2 => &tuple.elems,
+        _ => return Err(err()),
+    };
+    match (attr_lit(&elems[0]), attr_lit(&elems[1])) {
+        (Some(syn::Lit::Str(name)), Some(syn::Lit::Str(value))) => {
+            Ok((name.clone(), value.clone()))
+        }
+        _ => Err(err()),
+    }
+}
+
+fn parse_flag_attr_bound(expr: &syn::Expr) -> syn::Result<Bound> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => Ok(Bound {
+            expr: expr.clone(),
+            value: lit.value() as f64,
+            text: lit.value().to_string(),
+            is_int: true,
+        }),
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Float(lit),
+            ..
+        }) => Ok(Bound {
+            expr: expr.clone(),
+            value: lit.value(),
+            text: lit.value().to_string(),
+            is_int: false,
+        }),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr: inner,
+            ..
+        }) if !matches!(**inner, syn::Expr::Unary(_)) => {
+            let bound = parse_flag_attr_bound(inner)?;
+            Ok(Bound {
+                expr: expr.clone(),
+                value: -bound.value,
+                text: format!("-{}", bound.text),
+                is_int: bound.is_int,
+            })
+        }
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "Bound must be a number literal",
+        )),
+    }
+}
+
+fn parse_flag_attr_range(
+    expr: &syn::Expr,
+) -> syn::Result<(Option<Bound>, Option<Bound>)> {
+    match expr {
+        syn::Expr::Range(range) => {
+            if let (syn::RangeLimits::HalfOpen(_), Some(_)) =
+                (&range.limits, &range.to)
+            {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "Range must be inclusive, like 1..=256",
+                ));
+            }
+            let min =
+                range.from.as_ref().map(|from| parse_flag_attr_bound(from));
+            let max = range.to.as_ref().map(|to| parse_flag_attr_bound(to));
+            Ok((min.transpose()?, max.transpose()?))
+        }
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "Range must be a range of number literals, like 1..=256",
+        )),
+    }
+}
+
+fn parse_flag_attr_fn_path(expr: &syn::Expr) -> syn::Result<syn::Path> {
+    if let syn::Expr::Path(expr_path) = expr {
+        Ok(expr_path.path.clone())
+    } else {
+        Err(syn::Error::new_spanned(
+            expr,
+            "Expected a path to a function",
+        ))
+    }
+}
+
 fn generate_field_parsing(flag: &Flag) -> TokenStream {
     let name = &flag.name;
     let name_lit = name.to_string();
     let name_temp_var =
         syn::Ident::new(&format!("parsed_{}", name), name.span());
     let ty = &flag.ty;
+    let check_choice = flag.choices().map(|choices| {
+        quote! {
+            let input = ctflag::internal::check_choice(
+                #name_lit, input, &[#(#choices),*])?;
+        }
+    });
+    let check_range = if flag.has_bounds() {
+        let value_ty =
+            extract_option_param_type(ty).unwrap_or_else(|| ty.clone());
+        let is_float = matches!(extract_flag_type(&value_ty), FlagType::Float);
+        let min = bound_tokens(flag.attrs.min.as_ref(), is_float);
+        let max = bound_tokens(flag.attrs.max.as_ref(), is_float);
+        let check = quote! {
+            ctflag::internal::check_range(#name_lit, value, #min, #max)
+        };
+        Some(match &flag.flag_type {
+            FlagType::Option => quote! {
+                let value = value.map(|value| #check).transpose()?;
+            },
+            _ => quote! {
+                let value = #check?;
+            },
+        })
+    } else {
+        None
+    };
+    let validate = flag.attrs.validator.as_ref().map(|validator| {
+        match &flag.flag_type {
+            FlagType::Option => quote! {
+                if let Some(value) = &value {
+                    ctflag::internal::validate(#name_lit, value, #validator)?;
+                }
+            },
+            _ => quote! {
+                ctflag::internal::validate(#name_lit, &value, #validator)?;
+            },
+        }
+    });
+    let missing_value = quote! {
+        ctflag::FlagError::MissingValue(ctflag::MissingValueStruct {
+            flag: String::from(#name_lit),
+            index: None,
+        })
+    };
     let parse_expr = match &flag.flag_type {
         FlagType::Bool => {
-            quote_spanned! {name.span()=>
-                ctflag::internal::bool_from_arg(arg_value.as_ref().map(|s| s.as_str()))
-                    .map_err(|err| ctflag::FlagError::ParseError(
-                        ctflag::ParseErrorStruct {
-                            type_str: "bool",
-                            input: arg_value.unwrap(),
-                            src: err,
-                        }
-                    ))?
+            quote_spanned! {name.span()=> {
+                let value = ctflag::internal::parse_bool(#name_lit, arg_value)?;
+                #validate
+                value
+                }
             }
         }
-        FlagType::Option => quote_spanned! {name.span()=> {
-            let input = arg_value
-                .or_else(|| iter.next_arg())
-                .ok_or(ctflag::FlagError::MissingValue(String::from(#name_lit)))?;
-            ctflag::internal::option_from_arg(&input)
-                .map_err(|err| ctflag::FlagError::ParseError(
-                    ctflag::ParseErrorStruct {
-                        type_str: stringify!(#ty),
-                        input: input,
-                        src: err,
-                    }
-                ))?
+        _ if flag.is_os() => {
+            let from_arg_os = match &flag.flag_type {
+                FlagType::Option => {
+                    quote! { ctflag::internal::option_from_arg_os }
+                }
+                _ => quote! { ctflag::FromArgOs::from_arg_os },
+            };
+            quote_spanned! {name.span()=> {
+                let input = arg_value
+                    .or_else(|| iter.next_arg())
+                    .ok_or_else(|| #missing_value)?;
+                let input = std::convert::AsRef::<std::ffi::OsStr>::as_ref(&input);
+                let value: #ty = #from_arg_os(input).map_err(|err| {
+                    ctflag::internal::parse_error(
+                        #name_lit, stringify!(#ty), input.to_string_lossy(), err)
+                })?;
+                #check_range
+                #validate
+                value
+                }
             }
-        },
-        _ => quote_spanned! {name.span()=> {
-            let input = arg_value
-                .or_else(|| iter.next_arg())
-                .ok_or(ctflag::FlagError::MissingValue(String::from(#name_lit)))?;
-            ctflag::FromArg::from_arg(&input)
-                .map_err(|err| ctflag::FlagError::ParseError(
-                    ctflag::ParseErrorStruct {
-                        type_str: stringify!(#ty),
-                        input: input,
-                        src: err,
-                    }
-                ))?
+        }
+        flag_type => {
+            // Borrowed strings are the input itself, which can't fail.
+            let value = match flag_type {
+                FlagType::Option if flag.borrows() => quote! { Some(input) },
+                _ if flag.borrows() => quote! { input },
+                FlagType::Option => quote! {
+                    ctflag::internal::parse_value(
+                        #name_lit,
+                        stringify!(#ty),
+                        input,
+                        ctflag::internal::option_from_arg,
+                    )?
+                },
+                _ => quote! {
+                    ctflag::internal::parse_value(
+                        #name_lit,
+                        stringify!(#ty),
+                        input,
+                        ctflag::FromArg::from_arg,
+                    )?
+                },
+            };
+            quote_spanned! {name.span()=> {
+                let input = arg_value
+                    .or_else(|| iter.next_arg())
+                    .ok_or_else(|| #missing_value)?;
+                let input = ctflag::internal::ArgValue::into_str(input, #name_lit)?;
+                #check_choice
+                let value: #ty = #value;
+                #check_range
+                #validate
+                value
+                }
             }
-        },
+        }
     };
 
     let long_name = syn::LitStr::new(&format!("--{}", name), name.span());
@@ -463,64 +1517,172 @@ fn generate_field_parsing(flag: &Flag) -> TokenStream {
 
     quote_spanned! {name.span()=>
         #match_case => {
+            seen_flags.push((#name_lit, arg_index));
             #name_temp_var = #parse_expr ;
         }
     }
 }
 
-fn generate_flag_name_and_len(flag: &Flag) -> (String, usize) {
-    let mut buf = StringBuilder::new();
-    if let Some(short_name) = flag.short_name() {
-        buf.append(&format!("-{}, ", short_name.value()));
+// Validates the value of a flag that wasn't set.  `Option` fields are `None`
+// then, which isn't validated.
+fn generate_default_validation(flag: &Flag) -> Option<TokenStream> {
+    let validator = flag.attrs.validator.as_ref()?;
+    if let FlagType::Option = flag.flag_type {
+        return None;
+    }
+    let name = &flag.name;
+    let name_lit = name.to_string();
+    let name_temp_var =
+        syn::Ident::new(&format!("parsed_{}", name), name.span());
+    Some(quote! {
+        errors.check(ctflag::internal::validate_default(
+            &seen_flags, #name_lit, &#name_temp_var, #validator))?;
+    })
+}
+
+// A bool flag only counts as set for the relation checks when it's true, so
+// `--json=false` doesn't conflict with other flags.
+fn generate_unset_bool(flag: &Flag) -> Option<TokenStream> {
+    if let FlagType::Bool = flag.flag_type {
+        let name = &flag.name;
+        let name_lit = name.to_string();
+        let name_temp_var =
+            syn::Ident::new(&format!("parsed_{}", name), name.span());
+        Some(quote! {
+            if !#name_temp_var {
+                seen_flags.retain(|(flag, _)| *flag != #name_lit);
+            }
+        })
     } else {
-        buf.append("    ");
+        None
     }
-    buf.append(&format!("--{}", &flag.name));
-    match &flag.flag_type {
-        FlagType::Bool => {}
-        FlagType::Option => {
-            buf.append(&format!(" [{}]", flag.placeholder().unwrap_or("VALUE")))
+}
+
+fn generate_flag_info(flag: &Flag, conflicts_with: &[String]) -> TokenStream {
+    let name = flag.name.to_string();
+    let short = match flag.short_name() {
+        Some(short_name) => quote!(Some(#short_name)),
+        None => quote!(None),
+    };
+    let type_name = type_name(&flag.ty);
+    let value_ty =
+        extract_option_param_type(&flag.ty).unwrap_or_else(|| flag.ty.clone());
+    let flag_type = flag_type_tokens(&extract_flag_type(&value_ty));
+    let placeholder = match &flag.flag_type {
+        FlagType::Bool => quote!(None),
+        _ => {
+            let placeholder = flag.placeholder().unwrap_or("VALUE");
+            quote!(Some(#placeholder))
+        }
+    };
+    let optional = matches!(flag.flag_type, FlagType::Option);
+    let desc = match flag.description() {
+        Some(desc) => quote!(Some(#desc)),
+        None => quote!(None),
+    };
+    let default = match flag.default_value() {
+        Some(syn::Lit::Str(def)) => quote!(Some(#def)),
+        Some(def) => {
+            let def = def.clone().into_token_stream().to_string();
+            quote!(Some(#def))
         }
-        _ => buf.append(&format!(" {}", flag.placeholder().unwrap_or("VALUE"))),
+        None => quote!(None),
     };
-    let s = String::from(buf);
-    let len = s.as_str().graphemes(true).count();
-    (s, len)
+    let group = match flag.group() {
+        Some(group) => quote!(Some(#group)),
+        None => quote!(None),
+    };
+    let possible_values = match flag.choices() {
+        Some(choices) => quote!(&[#(#choices),*]),
+        None if flag.is_os() || flag.borrows() => quote!(&[]),
+        None => quote!(<#value_ty as ctflag::FromArg>::possible_values()),
+    };
+    let min = match &flag.attrs.min {
+        Some(min) => {
+            let text = &min.text;
+            quote!(Some(#text))
+        }
+        None => quote!(None),
+    };
+    let max = match &flag.attrs.max {
+        Some(max) => {
+            let text = &max.text;
+            quote!(Some(#text))
+        }
+        None => quote!(None),
+    };
+    let requires = &flag.attrs.requires;
+    let required_unless = &flag.attrs.required_unless;
+    let required_if = flag
+        .attrs
+        .required_if
+        .iter()
+        .map(|(name, value)| quote!((#name, #value)));
+    let complete = match flag.completer() {
+        Some(completer) => quote!(Some(#completer as ctflag::Completer)),
+        None => quote!(None),
+    };
+    quote! {
+        {
+            let mut info =
+                ctflag::FlagInfo::new(#name, #type_name, #flag_type);
+            info.short = #short;
+            info.placeholder = #placeholder;
+            info.description = #desc;
+            info.default = #default;
+            info.possible_values = #possible_values;
+            info.optional = #optional;
+            info.group = #group;
+            info.complete = #complete;
+            info.min = #min;
+            info.max = #max;
+            info.conflicts_with = &[#(#conflicts_with),*];
+            info.requires = &[#(#requires),*];
+            info.required_unless = &[#(#required_unless),*];
+            info.required_if = &[#(#required_if),*];
+            info
+        }
+    }
 }
 
-fn generate_description(flag: &[Flag]) -> TokenStream {
-    let flag_names = flag
-        .iter()
-        .map(generate_flag_name_and_len)
-        .collect::<Vec<(String, usize)>>();
-    let col_width = flag_names.iter().map(|(_, len)| len).max().unwrap();
-
-    let mut buf = StringBuilder::new();
-    buf.append("OPTIONS:\n");
-
-    for ((flag_name, len), flag) in flag_names.iter().zip(flag.iter()) {
-        buf.append("  ");
-        buf.append(flag_name);
-        if flag.description().is_some() || flag.default_value().is_some() {
-            buf.append("    ");
-            for _ in 0..(col_width - len) {
-                buf.append(" ");
-            }
-            if let Some(desc) = flag.description() {
-                buf.append(&desc);
-            }
-            if let Some(def) = flag.default_value() {
-                use quote::ToTokens;
-                buf.append(&format!(
-                    " (defaults to {})",
-                    &def.clone().into_token_stream()
-                ));
-            }
+fn flag_type_tokens(flag_type: &FlagType) -> TokenStream {
+    match flag_type {
+        FlagType::Bool => quote!(ctflag::FlagType::Bool),
+        FlagType::Int => quote!(ctflag::FlagType::Int),
+        FlagType::Float => quote!(ctflag::FlagType::Float),
+        FlagType::String => quote!(ctflag::FlagType::String),
+        FlagType::Option | FlagType::Other => quote!(ctflag::FlagType::Other),
+    }
+}
+
+// Formats a type the way it would be written by hand, without the spaces
+// that `TokenStream::to_string()` puts between every token.
+fn type_name(ty: &syn::Type) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+    let mut buf = String::new();
+    for token in ty.into_token_stream().to_string().split_whitespace() {
+        let prev = buf.chars().last();
+        let next = token.chars().next();
+        if (prev.is_some_and(is_word) && next.is_some_and(is_word))
+            || prev == Some(',')
+        {
+            buf.push(' ');
         }
-        buf.append("\n");
+        buf.push_str(token);
     }
-    let desc = String::from(buf);
+    buf
+}
+
+fn generate_group_info(group: &Group) -> TokenStream {
+    let name = &group.name;
+    let desc = match &group.description {
+        Some(desc) => quote!(Some(#desc)),
+        None => quote!(None),
+    };
     quote! {
-        String::from(#desc)
+        ctflag::GroupInfo {
+            name: #name,
+            description: #desc,
+        }
     }
 }